@@ -1,10 +1,14 @@
-use crate::decompressors::utils::normalize_mode;
-use crate::{DecompressError, Decompression, Decompressor, ExtractOpts, Listing};
+use crate::decompressors::limits::Budget;
+use crate::decompressors::utils::{
+    ensure_contained, listing, measure, normalize_mode, prepare_outpath, resolve_existing,
+    safe_join, set_times, skip_unsafe, EntryTimes,
+};
+use crate::{DecompressError, Decompression, Decompressor, Entry, ExtractOpts, Listing};
 use ar::Archive;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::path::{Component, PathBuf};
-use std::{fs, io};
+use std::fs;
+use std::path::PathBuf;
 use std::{
     fs::File,
     io::{BufReader, Read},
@@ -12,11 +16,11 @@ use std::{
 };
 
 lazy_static! {
-    static ref RE: Regex = Regex::new(r"(?i)\.ar$").unwrap();
+    static ref RE: Regex = Regex::new(r"(?i)\.(ar|a)$").unwrap();
 }
 
-fn build_archive(archive: &Path) -> Result<Archive<Box<dyn Read>>, DecompressError> {
-    let fd = BufReader::new(File::open(archive)?);
+fn build_archive(rdr: Box<dyn Read>) -> Result<Archive<Box<dyn Read>>, DecompressError> {
+    let fd = BufReader::new(rdr);
     let out: Archive<Box<dyn Read>> = Archive::new(Box::new(fd));
     Ok(out)
 }
@@ -45,96 +49,149 @@ impl Decompressor for Ar {
         archive
             .file_name()
             .and_then(std::ffi::OsStr::to_str)
-            .map_or(false, |f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
+            .is_some_and(|f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
     }
 
     fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
-        let mut out = build_archive(archive)?;
-        let mut entries = vec![];
-        while let Some(entry) = out.next_entry() {
-            let entry = entry?;
-            let header = entry.header();
-
-            let filepath = {
-                #[cfg(windows)]
-                {
-                    PathBuf::from(String::from_utf8_lossy(header.identifier()).to_string())
-                }
-                #[cfg(unix)]
-                {
-                    use std::ffi::OsStr;
-                    use std::os::unix::prelude::OsStrExt;
-                    PathBuf::from(OsStr::from_bytes(header.identifier()))
+        listing("ar", || {
+            let mut out = build_archive(Box::new(File::open(archive)?))?;
+            let mut entries = vec![];
+            while let Some(entry) = out.next_entry() {
+                let entry = entry?;
+                let header = entry.header();
+                if is_symbol_table(header.identifier()) {
+                    continue;
                 }
-            };
-            entries.push(filepath.to_string_lossy().to_string());
-        }
-        Ok(Listing { id: "ar", entries })
+
+                entries.push(Entry {
+                    path: member_path(header.identifier()),
+                    size: Some(header.size()),
+                    mode: Some(header.mode()),
+                    mtime: i64::try_from(header.mtime()).ok(),
+                    uid: Some(u64::from(header.uid())),
+                    gid: Some(u64::from(header.gid())),
+                    ..Entry::default()
+                });
+            }
+            Ok(Listing {
+                id: "ar",
+                entries,
+                package: None,
+            })
+        })
     }
 
     fn decompress(
         &self,
         archive: &Path,
         to: &Path,
-        _opts: &ExtractOpts,
+        opts: &ExtractOpts,
     ) -> Result<Decompression, DecompressError> {
-        let mut out = build_archive(archive)?;
-        let mut files = vec![];
-
-        if !to.exists() {
-            fs::create_dir_all(to)?;
-        }
-
-        // alternative impl: just unpack, and then mv everything back X levels
-        while let Some(entry) = out.next_entry() {
-            let entry = entry?;
-            let header = entry.header();
-
-            let filepath = {
-                #[cfg(windows)]
-                {
-                    PathBuf::from(String::from_utf8_lossy(header.identifier()).to_string())
-                }
-                #[cfg(unix)]
-                {
-                    use std::ffi::OsStr;
-                    use std::os::unix::prelude::OsStrExt;
-                    PathBuf::from(OsStr::from_bytes(header.identifier()))
-                }
-            };
+        self.decompress_reader(Box::new(File::open(archive)?), to, opts)
+    }
 
-            if filepath.components().any(|component| match component {
-                Component::ParentDir | Component::RootDir | Component::Prefix(..) => true,
-                Component::Normal(..) | Component::CurDir => false,
-            }) {
-                continue;
-            }
+    fn decompress_reader(
+        &self,
+        reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        measure("ar", reader, opts, |reader, budget| {
+            ar_extract(&mut build_archive(Box::new(reader))?, to, opts, budget)
+        })
+    }
+}
 
-            // guess what, ar archives don't support components, only 1 level is there, so stripping not relevant!
-            // so does create_dir_all'isms
+fn ar_extract(
+    out: &mut Archive<Box<dyn Read>>,
+    to: &Path,
+    opts: &ExtractOpts,
+    mut budget: Budget,
+) -> Result<Decompression, DecompressError> {
+    let mut report = Decompression::default();
 
-            // because we potentially stripped a component, we may have an empty path, in which case
-            // the joined target will be identical to the target folder
-            // we take this approach to avoid hardcoding a check against empty ""
-            let outpath = to.join(filepath);
-            if to == outpath {
-                continue;
-            }
+    if !to.exists() {
+        fs::create_dir_all(to)?;
+    }
 
-            #[cfg(unix)]
-            let mode = entry.header().mode();
+    // alternative impl: just unpack, and then mv everything back X levels
+    while let Some(entry) = out.next_entry() {
+        let res = extract_entry(entry?, to, opts, &mut report, &mut budget);
+        skip_unsafe(res, &mut report, opts)?;
+    }
+    Ok(report)
+}
 
-            let mut outfile = fs::File::create(&outpath)?;
-            io::copy(&mut BufReader::new(entry), &mut outfile)?;
-            files.push(outpath.to_string_lossy().to_string());
+fn extract_entry(
+    entry: ar::Entry<'_, Box<dyn Read>>,
+    to: &Path,
+    opts: &ExtractOpts,
+    report: &mut Decompression,
+    budget: &mut Budget,
+) -> Result<(), DecompressError> {
+    let header = entry.header();
+
+    if is_symbol_table(header.identifier()) {
+        return Ok(());
+    }
+    let filepath = member_path(header.identifier());
 
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let mode = normalize_mode(mode);
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
-            }
-        }
-        Ok(Decompression { id: "ar", files })
+    // guess what, ar archives don't support components, only 1 level is there, so stripping not relevant!
+    // so does create_dir_all'isms
+    let outpath = safe_join(to, &filepath)?;
+    if to == outpath {
+        return Ok(());
+    }
+
+    if !(opts.filter)(outpath.as_path()) {
+        report.filtered.push(outpath.to_string_lossy().to_string());
+        return Ok(());
+    }
+
+    let outpath = (opts.map)(outpath.as_path());
+    ensure_contained(to, &outpath)?;
+    budget.entry(to, &outpath)?;
+
+    #[cfg(unix)]
+    let mode = header.mode();
+    let times = EntryTimes::mtime(i64::try_from(header.mtime()).unwrap_or_default());
+
+    let outpath = match resolve_existing(&outpath, times.mtime, opts, report)? {
+        Some(outpath) => outpath,
+        None => return Ok(()),
+    };
+    prepare_outpath(to, &outpath)?;
+    let mut outfile = fs::File::create(&outpath)?;
+    report.bytes_written += budget.copy(&mut BufReader::new(entry), &mut outfile)?;
+    report.files.push(outpath.to_string_lossy().to_string());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = normalize_mode(mode);
+        fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+    }
+    set_times(&outpath, times, opts)?;
+    Ok(())
+}
+
+/// The path of a member. Long GNU (`//` name table) and BSD (`#1/`) names are resolved by
+/// the `ar` crate, which detects the variant of the archive.
+fn member_path(identifier: &[u8]) -> PathBuf {
+    #[cfg(windows)]
+    {
+        PathBuf::from(String::from_utf8_lossy(identifier).to_string())
     }
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::prelude::OsStrExt;
+        PathBuf::from(OsStr::from_bytes(identifier))
+    }
+}
+
+/// Symbol tables are an index for the linker, not members. The `ar` crate skips GNU tables and
+/// named BSD ones, but not a BSD table that is the first member under its short name.
+fn is_symbol_table(identifier: &[u8]) -> bool {
+    identifier == b"__.SYMDEF" || identifier == b"__.SYMDEF SORTED"
 }
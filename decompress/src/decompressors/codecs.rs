@@ -0,0 +1,518 @@
+use std::sync::Arc;
+
+use crate::decompressors::compose::Codec;
+// the container formats need no codec on their own
+#[cfg(any(
+    feature = "gz",
+    feature = "targz",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm",
+    feature = "xz",
+    feature = "tarxz",
+    feature = "bz2",
+    feature = "tarbz",
+    feature = "zstd",
+    feature = "tarzst",
+    feature = "lz4",
+    feature = "tarlz4",
+    feature = "br",
+    feature = "tarbr",
+    feature = "lz",
+    feature = "tarlz",
+    feature = "lzma",
+    feature = "tarlzma",
+    feature = "z",
+    feature = "tarz"
+))]
+use {
+    crate::DecompressError,
+    std::io::{BufRead, Read},
+};
+
+/// The codecs enabled by features, the order in which containers detect them by magic bytes
+#[must_use]
+pub fn all() -> Vec<Arc<dyn Codec>> {
+    vec![
+        #[cfg(any(
+            feature = "gz",
+            feature = "targz",
+            feature = "cpio",
+            feature = "deb",
+            feature = "rpm"
+        ))]
+        Arc::new(Gzip),
+        #[cfg(any(
+            feature = "xz",
+            feature = "tarxz",
+            feature = "cpio",
+            feature = "deb",
+            feature = "rpm"
+        ))]
+        Arc::new(Xz),
+        #[cfg(any(feature = "bz2", feature = "tarbz", feature = "deb", feature = "rpm"))]
+        Arc::new(Bzip2),
+        #[cfg(any(
+            feature = "zstd",
+            feature = "tarzst",
+            feature = "cpio",
+            feature = "deb",
+            feature = "rpm"
+        ))]
+        Arc::new(Zstd),
+        #[cfg(any(feature = "lz4", feature = "tarlz4"))]
+        Arc::new(Lz4),
+        #[cfg(any(feature = "br", feature = "tarbr"))]
+        Arc::new(Brotli),
+        #[cfg(any(feature = "lz", feature = "tarlz"))]
+        Arc::new(Lzip),
+        #[cfg(any(
+            feature = "lzma",
+            feature = "tarlzma",
+            feature = "deb",
+            feature = "rpm"
+        ))]
+        Arc::new(Lzma),
+        #[cfg(any(feature = "z", feature = "tarz"))]
+        Arc::new(Compress),
+    ]
+}
+
+/// The enabled codec with an extension (`gz`, as in `data.tar.gz`)
+#[must_use]
+pub fn by_extension(ext: &str) -> Option<Arc<dyn Codec>> {
+    all().into_iter().find(|codec| {
+        codec
+            .extensions()
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(ext))
+    })
+}
+
+/// The enabled codec with a name (`gzip`, as an rpm records its payload compressor)
+#[must_use]
+pub fn by_name(name: &str) -> Option<Arc<dyn Codec>> {
+    all().into_iter().find(|codec| codec.name() == name)
+}
+
+#[cfg(any(
+    feature = "gz",
+    feature = "targz",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm"
+))]
+pub struct Gzip;
+
+#[cfg(any(
+    feature = "gz",
+    feature = "targz",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm"
+))]
+impl Codec for Gzip {
+    fn id(&self) -> &'static str {
+        "gz"
+    }
+
+    fn name(&self) -> &'static str {
+        "gzip"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["gz"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        Some("application/gzip")
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        head.starts_with(&[0x1f, 0x8b])
+    }
+
+    fn recorded(&self, head: &[u8]) -> crate::decompressors::compose::Recorded {
+        let dec = flate2::bufread::GzDecoder::new(head);
+        let Some(header) = dec.header() else {
+            return crate::decompressors::compose::Recorded::default();
+        };
+        crate::decompressors::compose::Recorded {
+            name: header.filename().and_then(recorded_name),
+            // zero means no time was recorded
+            mtime: Some(header.mtime())
+                .filter(|mtime| *mtime > 0)
+                .map(i64::from),
+        }
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        Ok(Box::new(flate2::bufread::GzDecoder::new(reader)))
+    }
+}
+
+#[cfg(any(
+    feature = "gz",
+    feature = "targz",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm"
+))]
+/// Turn a name recorded inside a stream (e.g. gzip `FNAME`) into a safe output name,
+/// keeping only its last component so it cannot point outside of the target folder
+fn recorded_name(name: &[u8]) -> Option<std::ffi::OsString> {
+    use std::ffi::OsStr;
+    use std::path::Path;
+
+    let name = {
+        #[cfg(windows)]
+        {
+            std::ffi::OsString::from(String::from_utf8_lossy(name).to_string())
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::prelude::OsStrExt;
+            OsStr::from_bytes(name).to_os_string()
+        }
+    };
+    Path::new(&name).file_name().map(OsStr::to_os_string)
+}
+
+#[cfg(any(
+    feature = "xz",
+    feature = "tarxz",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm"
+))]
+pub struct Xz;
+
+#[cfg(any(
+    feature = "xz",
+    feature = "tarxz",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm"
+))]
+impl Codec for Xz {
+    fn id(&self) -> &'static str {
+        "xz"
+    }
+
+    fn name(&self) -> &'static str {
+        "xz"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xz"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        Some("application/x-xz")
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00])
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        Ok(Box::new(xz::bufread::XzDecoder::new(reader)))
+    }
+}
+
+#[cfg(any(feature = "bz2", feature = "tarbz", feature = "deb", feature = "rpm"))]
+pub struct Bzip2;
+
+#[cfg(any(feature = "bz2", feature = "tarbz", feature = "deb", feature = "rpm"))]
+impl Codec for Bzip2 {
+    fn id(&self) -> &'static str {
+        "bz"
+    }
+
+    fn name(&self) -> &'static str {
+        "bzip2"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["bz2", "bz"]
+    }
+
+    fn abbreviations(&self) -> &'static [&'static str] {
+        &["bz"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        Some("application/x-bzip2")
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        head.starts_with(b"BZh")
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        Ok(Box::new(bzip2::bufread::BzDecoder::new(reader)))
+    }
+}
+
+#[cfg(any(
+    feature = "zstd",
+    feature = "tarzst",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm"
+))]
+pub struct Zstd;
+
+#[cfg(any(
+    feature = "zstd",
+    feature = "tarzst",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm"
+))]
+impl Codec for Zstd {
+    fn id(&self) -> &'static str {
+        "zst"
+    }
+
+    fn name(&self) -> &'static str {
+        "zstd"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["zst"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        Some("application/zstd")
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd])
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        Ok(Box::new(zstd::stream::read::Decoder::with_buffer(reader)?))
+    }
+}
+
+#[cfg(any(feature = "lz4", feature = "tarlz4"))]
+pub struct Lz4;
+
+#[cfg(any(feature = "lz4", feature = "tarlz4"))]
+impl Codec for Lz4 {
+    fn id(&self) -> &'static str {
+        "lz4"
+    }
+
+    fn name(&self) -> &'static str {
+        "lz4"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["lz4"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        Some("application/x-lz4")
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        head.starts_with(&[0x04, 0x22, 0x4d, 0x18])
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        Ok(Box::new(lz4_flex::frame::FrameDecoder::new(reader)))
+    }
+}
+
+#[cfg(any(feature = "br", feature = "tarbr"))]
+pub struct Brotli;
+
+#[cfg(any(feature = "br", feature = "tarbr"))]
+impl Codec for Brotli {
+    fn id(&self) -> &'static str {
+        "br"
+    }
+
+    fn name(&self) -> &'static str {
+        "brotli"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["br"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn test_magic(&self, _head: &[u8]) -> bool {
+        // brotli streams have no magic to detect them by
+        false
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        Ok(Box::new(brotli_decompressor::Decompressor::new(
+            reader, 4096,
+        )))
+    }
+}
+
+#[cfg(any(feature = "lz", feature = "tarlz"))]
+pub struct Lzip;
+
+#[cfg(any(feature = "lz", feature = "tarlz"))]
+impl Codec for Lzip {
+    fn id(&self) -> &'static str {
+        "lz"
+    }
+
+    fn name(&self) -> &'static str {
+        "lzip"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["lz"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        Some("application/x-lzip")
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        head.starts_with(b"LZIP")
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        Ok(Box::new(
+            crate::decompressors::lzip_common::LzipDecoder::new(reader),
+        ))
+    }
+}
+
+#[cfg(any(
+    feature = "lzma",
+    feature = "tarlzma",
+    feature = "deb",
+    feature = "rpm"
+))]
+/// The largest dictionary an LZMA-alone header is trusted with, as `lzma -9` uses 64 MiB
+const LZMA_MAX_DICT_SIZE: u64 = 1 << 29;
+
+#[cfg(any(
+    feature = "lzma",
+    feature = "tarlzma",
+    feature = "deb",
+    feature = "rpm"
+))]
+pub struct Lzma;
+
+#[cfg(any(
+    feature = "lzma",
+    feature = "tarlzma",
+    feature = "deb",
+    feature = "rpm"
+))]
+impl Codec for Lzma {
+    fn id(&self) -> &'static str {
+        "lzma"
+    }
+
+    fn name(&self) -> &'static str {
+        "lzma"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["lzma"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        Some("application/x-lzma")
+    }
+
+    fn test_magic(&self, _head: &[u8]) -> bool {
+        // LZMA-alone has no magic, only likely properties, which is too weak to go by here
+        false
+    }
+
+    fn decoder<'a>(
+        &self,
+        mut reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        // the decoder allocates the dictionary the header asks for up front
+        if let Some(dict_size) = reader
+            .fill_buf()?
+            .get(1..5)
+            .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]))
+            .filter(|size| u64::from(*size) > LZMA_MAX_DICT_SIZE)
+        {
+            return Err(DecompressError::unsupported(format!(
+                "LZMA dictionary of {dict_size} bytes"
+            )));
+        }
+        let stream = xz::stream::Stream::new_lzma_decoder(LZMA_MAX_DICT_SIZE + (1 << 20))
+            .map_err(std::io::Error::from)?;
+        Ok(Box::new(xz::bufread::XzDecoder::new_stream(reader, stream)))
+    }
+}
+
+#[cfg(any(feature = "z", feature = "tarz"))]
+pub struct Compress;
+
+#[cfg(any(feature = "z", feature = "tarz"))]
+impl Codec for Compress {
+    fn id(&self) -> &'static str {
+        "z"
+    }
+
+    fn name(&self) -> &'static str {
+        "compress"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["z"]
+    }
+
+    fn abbreviations(&self) -> &'static [&'static str] {
+        &["az"]
+    }
+
+    fn mimetype(&self) -> Option<&'static str> {
+        Some("application/x-compress")
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        head.starts_with(&[0x1f, 0x9d])
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError> {
+        Ok(Box::new(crate::decompressors::lzw_common::LzwDecoder::new(
+            reader,
+        )))
+    }
+}
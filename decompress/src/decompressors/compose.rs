@@ -0,0 +1,322 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+#[cfg(any(
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+pub use crate::decompressors::stream_common::Stream;
+
+use crate::decompressors::limits::Budget;
+use crate::decompressors::utils::{listing, measure};
+use crate::{DecompressError, Decompression, Decompressor, Entry, ExtractOpts, Listing};
+
+/// How much of a decoded stream containers test their magic against
+pub const HEAD_SIZE: usize = 512;
+
+lazy_static! {
+    static ref IDS: Mutex<HashSet<&'static str>> = Mutex::default();
+    static ref REGEXES: Mutex<HashMap<String, Regex>> = Mutex::default();
+}
+
+///
+/// A stream codec (gzip, xz, zstd, ...), which wraps a container or a single file
+pub trait Codec {
+    /// Appended to a container's extension to make the id of their composition (`targz`)
+    fn id(&self) -> &'static str;
+
+    /// Extensions of the codec, appended to a container's (`.tar.gz`)
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Extensions of the codec following an abbreviated container (`.tgz`)
+    fn abbreviations(&self) -> &'static [&'static str] {
+        self.extensions()
+    }
+
+    /// The mimetype compositions of this codec answer to, when detecting by content
+    fn mimetype(&self) -> Option<&'static str>;
+
+    /// The name of the codec, as packages record their payload compressor (`gzip`)
+    fn name(&self) -> &'static str;
+
+    /// Test if the first bytes of a stream are in this codec
+    fn test_magic(&self, head: &[u8]) -> bool;
+
+    /// What the stream records of the file it compresses (gzip `FNAME` and `MTIME`), given
+    /// its first bytes. Nothing by default.
+    fn recorded(&self, _head: &[u8]) -> Recorded {
+        Recorded::default()
+    }
+
+    /// Wrap a stream in a decoder
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the decoder cannot be set up
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn BufRead + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, DecompressError>;
+}
+
+/// The name and modification time of a compressed file, as its stream records them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recorded {
+    /// A file name, without folders
+    pub name: Option<OsString>,
+    /// Seconds since the unix epoch
+    pub mtime: Option<i64>,
+}
+
+///
+/// A container of entries (tar, cpio, ...), read as a stream
+pub trait Container {
+    /// The id of the container when not compressed (`tarball`)
+    fn id(&self) -> &'static str;
+
+    /// The extension of the container (`tar`)
+    fn extension(&self) -> &'static str;
+
+    /// The abbreviation of the container for compressed archives (`t` as in `.tgz`)
+    fn abbreviation(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The mimetype of the container when not compressed
+    fn mimetype(&self) -> &'static str;
+
+    /// Test if the first bytes of a container, up to `HEAD_SIZE`, are in this format
+    fn test_magic(&self, head: &[u8]) -> bool;
+
+    /// List the entries of a container
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading or parsing fails
+    fn list(&self, reader: Box<dyn Read>) -> Result<Vec<Entry>, DecompressError>;
+
+    /// Extract a container to `to`, accounting for what is written in `budget`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if unpacking fails
+    fn extract(
+        &self,
+        reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+        budget: &mut Budget,
+    ) -> Result<Decompression, DecompressError>;
+}
+
+///
+/// A container, compressed with a codec or not. Without a codec, a container is also read
+/// when compressed with one of a list of codecs (given with `with_codecs`), told apart by
+/// their magic bytes, as archives like initramfs images rarely have a telling name.
+pub struct Composed {
+    id: &'static str,
+    container: Arc<dyn Container>,
+    codec: Option<Arc<dyn Codec>>,
+    codecs: Vec<Arc<dyn Codec>>,
+    re: Regex,
+}
+
+impl Composed {
+    /// Compose a container and a codec, `re` replaces the regex made of their extensions
+    #[must_use]
+    pub fn new(
+        container: Arc<dyn Container>,
+        codec: Option<Arc<dyn Codec>>,
+        re: Option<Regex>,
+    ) -> Self {
+        let id = match &codec {
+            Some(codec) => intern(format!("{}{}", container.extension(), codec.id())),
+            None => container.id(),
+        };
+        let re = re.unwrap_or_else(|| name_regex(container.as_ref(), codec.as_deref()));
+        Self {
+            id,
+            container,
+            codec,
+            codecs: Vec::new(),
+            re,
+        }
+    }
+
+    #[must_use]
+    pub fn build(
+        container: Arc<dyn Container>,
+        codec: Option<Arc<dyn Codec>>,
+        re: Option<Regex>,
+    ) -> Box<Self> {
+        Box::new(Self::new(container, codec, re))
+    }
+
+    /// Codecs to detect by magic bytes, when the container is not composed with a codec
+    #[must_use]
+    pub fn with_codecs(mut self, codecs: Vec<Arc<dyn Codec>>) -> Self {
+        self.codecs = codecs;
+        self
+    }
+
+    fn decoder(&self, reader: Box<dyn Read>) -> Result<Box<dyn Read>, DecompressError> {
+        let mut reader = BufReader::new(reader);
+        if let Some(codec) = &self.codec {
+            return codec.decoder(Box::new(reader));
+        }
+        let head = reader.fill_buf()?;
+        match self.codecs.iter().find(|codec| codec.test_magic(head)) {
+            Some(codec) => codec.decoder(Box::new(reader)),
+            None => Ok(Box::new(reader)),
+        }
+    }
+}
+
+impl Decompressor for Composed {
+    fn test_mimetype(&self, mimetype: &str) -> bool {
+        match &self.codec {
+            Some(codec) => codec.mimetype() == Some(mimetype),
+            None => self.container.mimetype() == mimetype,
+        }
+    }
+
+    fn test_content(&self, content: &mut dyn Read) -> bool {
+        // the codec's mimetype is shared with the codec on its own and the other containers,
+        // what it decodes to tells them apart
+        let Some(codec) = &self.codec else {
+            return true;
+        };
+        let Ok(decoder) = codec.decoder(Box::new(BufReader::new(content))) else {
+            return false;
+        };
+        let mut head = Vec::with_capacity(HEAD_SIZE);
+        // a stream cut short still has its first bytes decoded
+        let _ = decoder.take(HEAD_SIZE as u64).read_to_end(&mut head);
+        self.container.test_magic(&head)
+    }
+
+    fn test(&self, archive: &Path) -> bool {
+        archive
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .is_some_and(|f| self.re.is_match(f))
+    }
+
+    fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
+        listing(self.id, || {
+            Ok(Listing {
+                id: self.id,
+                entries: self
+                    .container
+                    .list(self.decoder(Box::new(File::open(archive)?))?)?,
+                package: None,
+            })
+        })
+    }
+
+    fn decompress(
+        &self,
+        archive: &Path,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        self.decompress_reader(Box::new(File::open(archive)?), to, opts)
+    }
+
+    fn decompress_reader(
+        &self,
+        reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        measure(self.id, reader, opts, |reader, mut budget| {
+            let reader = self.decoder(Box::new(reader))?;
+            self.container.extract(reader, to, opts, &mut budget)
+        })
+    }
+}
+
+/// Every composition of `containers` and `codecs`, followed by the containers on their own,
+/// which detect the codecs by magic bytes
+#[must_use]
+pub fn compose(
+    containers: &[Arc<dyn Container>],
+    codecs: &[Arc<dyn Codec>],
+) -> Vec<Box<dyn Decompressor>> {
+    let composed = containers.iter().flat_map(|container| {
+        codecs.iter().map(|codec| -> Box<dyn Decompressor> {
+            Composed::build(container.clone(), Some(codec.clone()), None)
+        })
+    });
+    let bare = containers.iter().map(|container| -> Box<dyn Decompressor> {
+        Box::new(Composed::new(container.clone(), None, None).with_codecs(codecs.to_vec()))
+    });
+    composed.chain(bare).collect()
+}
+
+/// `(?i)\.tar$` on its own, `(?i)\.(tar\.(gz)|t(gz))$` with a codec
+fn name_regex(container: &dyn Container, codec: Option<&dyn Codec>) -> Regex {
+    let container_ext = regex::escape(container.extension());
+    let pattern = match codec {
+        None => format!(r"(?i)\.{container_ext}$"),
+        Some(codec) => {
+            let mut names = vec![format!(
+                r"{container_ext}\.({})",
+                alternatives(codec.extensions())
+            )];
+            if let Some(abbreviation) = container.abbreviation() {
+                names.push(format!(
+                    "{}({})",
+                    regex::escape(abbreviation),
+                    alternatives(codec.abbreviations())
+                ));
+            }
+            format!(r"(?i)\.({})$", names.join("|"))
+        }
+    };
+    cached_regex(pattern)
+}
+
+pub(crate) fn alternatives(exts: &[&str]) -> String {
+    exts.iter()
+        .map(|ext| regex::escape(ext))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Compiled once, like the regexes of the other decompressors
+pub(crate) fn cached_regex(pattern: String) -> Regex {
+    let mut regexes = REGEXES.lock().unwrap_or_else(PoisonError::into_inner);
+    regexes
+        .entry(pattern)
+        .or_insert_with_key(|pattern| Regex::new(pattern).expect("extensions make a valid regex"))
+        .clone()
+}
+
+/// Ids of compositions live as long as the program, like those of the other decompressors,
+/// there is one for each composition ever made
+fn intern(id: String) -> &'static str {
+    let mut ids = IDS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(id) = ids.get(id.as_str()) {
+        return id;
+    }
+    let id: &'static str = Box::leak(id.into_boxed_str());
+    ids.insert(id);
+    id
+}
@@ -0,0 +1,132 @@
+use std::{io::Read, path::Path, sync::Arc};
+
+use crate::decompressors::compose::Container;
+use crate::decompressors::limits::Budget;
+use crate::{DecompressError, Decompression, Entry, ExtractOpts};
+
+/// The containers enabled by features
+#[must_use]
+pub fn all() -> Vec<Arc<dyn Container>> {
+    vec![
+        #[cfg(feature = "tarball")]
+        Arc::new(Tar),
+        #[cfg(feature = "cpio")]
+        Arc::new(Cpio),
+    ]
+}
+
+#[cfg(feature = "tarball")]
+pub struct Tar;
+
+#[cfg(feature = "tarball")]
+impl Container for Tar {
+    fn id(&self) -> &'static str {
+        "tarball"
+    }
+
+    fn extension(&self) -> &'static str {
+        "tar"
+    }
+
+    fn abbreviation(&self) -> Option<&'static str> {
+        Some("t")
+    }
+
+    fn mimetype(&self) -> &'static str {
+        "application/x-tar"
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        // POSIX and GNU archives have a magic, older ones only a header checksum
+        if head.get(257..262) == Some(b"ustar") {
+            return true;
+        }
+        let Some(header) = head.get(..512) else {
+            return false;
+        };
+        let Some(checksum) = std::str::from_utf8(&header[148..156])
+            .ok()
+            .and_then(|field| u32::from_str_radix(field.trim_matches([' ', '\0']), 8).ok())
+        else {
+            return false;
+        };
+        // the checksum field itself counts as spaces
+        let sum: u32 = header
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| {
+                if (148..156).contains(&i) {
+                    32
+                } else {
+                    u32::from(*byte)
+                }
+            })
+            .sum();
+        sum == checksum
+    }
+
+    fn list(&self, reader: Box<dyn Read>) -> Result<Vec<Entry>, DecompressError> {
+        crate::decompressors::tar_common::tar_list(&mut tar::Archive::new(reader))
+    }
+
+    fn extract(
+        &self,
+        reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+        budget: &mut Budget,
+    ) -> Result<Decompression, DecompressError> {
+        crate::decompressors::tar_common::tar_extract(
+            &mut tar::Archive::new(reader),
+            to,
+            opts,
+            budget,
+        )
+    }
+}
+
+/// newc, crc and odc cpio archives
+#[cfg(feature = "cpio")]
+pub struct Cpio;
+
+#[cfg(feature = "cpio")]
+impl Container for Cpio {
+    fn id(&self) -> &'static str {
+        "cpio"
+    }
+
+    fn extension(&self) -> &'static str {
+        "cpio"
+    }
+
+    fn mimetype(&self) -> &'static str {
+        "application/x-cpio"
+    }
+
+    fn test_magic(&self, head: &[u8]) -> bool {
+        [b"070701", b"070702", b"070707"]
+            .iter()
+            .any(|magic| head.starts_with(*magic))
+    }
+
+    fn list(&self, reader: Box<dyn Read>) -> Result<Vec<Entry>, DecompressError> {
+        crate::decompressors::cpio_common::cpio_list(
+            &mut crate::decompressors::cpio_common::CpioReader::new(reader),
+        )
+    }
+
+    fn extract(
+        &self,
+        reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+        budget: &mut Budget,
+    ) -> Result<Decompression, DecompressError> {
+        crate::decompressors::cpio_common::cpio_extract(
+            &mut crate::decompressors::cpio_common::CpioReader::new(reader),
+            to,
+            opts,
+            budget,
+        )
+    }
+}
@@ -0,0 +1,586 @@
+use std::collections::HashMap;
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::decompressors::limits::Budget;
+use crate::decompressors::links::{extract_hardlink, extract_link, Copies, Link};
+use crate::decompressors::utils::{
+    ensure_contained, normalize_mode, prepare_outpath, resolve_existing, safe_join, set_times,
+    skip_unsafe, EntryTimes, PendingDirs,
+};
+use crate::{DecompressError, Decompression, Entry, EntryKind, ErrorContext, ExtractOpts};
+
+const TRAILER: &[u8] = b"TRAILER!!!";
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFREG: u32 = 0o100_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// Symlink targets are stored as entry data, anything longer is not a path
+const MAX_LINK_TARGET: u64 = 4096;
+
+/// A cpio entry header, in one of the ASCII formats: "new" (`070701`), its checksummed
+/// variant "crc" (`070702`), or the portable "odc" (`070707`)
+pub struct Header {
+    pub path: PathBuf,
+    pub ino: u64,
+    pub mode: u32,
+    pub uid: u64,
+    pub gid: u64,
+    pub nlink: u64,
+    pub mtime: i64,
+    pub size: u64,
+    pub dev: (u64, u64),
+}
+
+impl Header {
+    fn kind(&self) -> EntryKind {
+        match self.mode & S_IFMT {
+            S_IFREG => EntryKind::File,
+            S_IFDIR => EntryKind::Directory,
+            S_IFLNK => EntryKind::Symlink,
+            _ => EntryKind::Other,
+        }
+    }
+
+    /// Regular files sharing an inode are hardlinks, most writers store their data once,
+    /// with the last of them
+    fn link_key(&self) -> Option<(u64, u64, u64)> {
+        (self.kind() == EntryKind::File && self.nlink > 1)
+            .then_some((self.dev.0, self.dev.1, self.ino))
+    }
+
+    fn permissions(&self) -> u32 {
+        self.mode & 0o7777
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Newc,
+    Crc,
+    Odc,
+}
+
+impl Format {
+    /// Width of each header field after the magic, and their radix
+    fn fields(self) -> (&'static [usize], u32) {
+        match self {
+            Self::Newc | Self::Crc => (&[8; 13], 16),
+            Self::Odc => (&[6, 6, 6, 6, 6, 6, 6, 11, 6, 11], 8),
+        }
+    }
+
+    /// Padding after `n` bytes of an entry, newc and crc align names and data to 4 bytes
+    fn pad(self, n: u64) -> u64 {
+        match self {
+            Self::Newc | Self::Crc => (4 - n % 4) % 4,
+            Self::Odc => 0,
+        }
+    }
+}
+
+/// Reads a cpio archive as a sequence of headers, each followed by its data, which is read
+/// through the `Read` impl of the reader
+pub struct CpioReader<R> {
+    inner: R,
+    /// Bytes of the current entry data not read yet
+    remaining: u64,
+    /// Padding after the current entry data
+    pad: u64,
+    /// Checksum of the current entry, for the crc format: recorded and computed so far
+    checksum: Option<(u32, u32)>,
+}
+
+impl<R: Read> CpioReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            pad: 0,
+            checksum: None,
+        }
+    }
+
+    /// Skip what is left of the current entry and read the next header, `None` at the end of
+    /// the archive
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for a malformed header, or on IO failure
+    pub fn next_header(&mut self) -> Result<Option<Header>, DecompressError> {
+        let rest = self.remaining + self.pad;
+        if io::copy(&mut (&mut self.inner).take(rest), &mut io::sink())? != rest {
+            return Err(truncated());
+        }
+        self.remaining = 0;
+        self.pad = 0;
+        self.checksum = None;
+
+        let mut magic = [0u8; 6];
+        self.inner.read_exact(&mut magic)?;
+        let format = match &magic {
+            b"070701" => Format::Newc,
+            b"070702" => Format::Crc,
+            b"070707" => Format::Odc,
+            [0xc7, 0x71, ..] | [0x71, 0xc7, ..] => {
+                return Err(DecompressError::unsupported("binary cpio header"))
+            }
+            _ => {
+                return Err(DecompressError::corrupt(format!(
+                    "unsupported cpio header: `{}`",
+                    String::from_utf8_lossy(&magic)
+                )))
+            }
+        };
+        let (widths, radix) = format.fields();
+        let mut raw = vec![0u8; widths.iter().sum()];
+        self.inner.read_exact(&mut raw)?;
+        let mut f = Vec::with_capacity(widths.len());
+        let mut at = 0;
+        for width in widths {
+            let field = std::str::from_utf8(&raw[at..at + width])
+                .ok()
+                .and_then(|field| u64::from_str_radix(field, radix).ok())
+                .ok_or_else(|| DecompressError::corrupt("malformed cpio header"))?;
+            f.push(field);
+            at += width;
+        }
+
+        let header = match format {
+            Format::Newc | Format::Crc => Header {
+                path: PathBuf::new(),
+                ino: f[0],
+                mode: u32::try_from(f[1]).unwrap_or(0),
+                uid: f[2],
+                gid: f[3],
+                nlink: f[4],
+                mtime: i64::try_from(f[5]).unwrap_or(0),
+                size: f[6],
+                dev: (f[7], f[8]),
+            },
+            Format::Odc => Header {
+                path: PathBuf::new(),
+                dev: (f[0], 0),
+                ino: f[1],
+                mode: u32::try_from(f[2]).unwrap_or(0),
+                uid: f[3],
+                gid: f[4],
+                nlink: f[5],
+                mtime: i64::try_from(f[7]).unwrap_or(0),
+                size: f[9],
+            },
+        };
+        let (namesize, check) = match format {
+            Format::Newc | Format::Crc => (f[11], f[12]),
+            Format::Odc => (f[8], 0),
+        };
+
+        if namesize == 0 || namesize > 4096 {
+            return Err(DecompressError::corrupt("malformed cpio header"));
+        }
+        // the name is NUL terminated, and padded along with the header
+        let header_size = (magic.len() + raw.len()) as u64;
+        let mut name =
+            vec![0u8; usize::try_from(namesize + format.pad(header_size + namesize)).unwrap_or(0)];
+        self.inner.read_exact(&mut name)?;
+        name.truncate(usize::try_from(namesize - 1).unwrap_or(0));
+        if name == TRAILER {
+            return Ok(None);
+        }
+
+        self.remaining = header.size;
+        self.pad = format.pad(header.size);
+        if format == Format::Crc {
+            self.checksum = Some((u32::try_from(check).unwrap_or(0), 0));
+        }
+        Ok(Some(Header {
+            path: bytes_path(&name),
+            ..header
+        }))
+    }
+}
+
+impl<R: Read> Read for CpioReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = usize::try_from(self.remaining)
+            .unwrap_or(usize::MAX)
+            .min(buf.len());
+        if max == 0 {
+            return Ok(0);
+        }
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= n as u64;
+
+        // the crc format records the sum of the data bytes
+        if let Some((recorded, sum)) = &mut self.checksum {
+            *sum = buf[..n]
+                .iter()
+                .fold(*sum, |sum, b| sum.wrapping_add(u32::from(*b)));
+            if self.remaining == 0 && sum != recorded {
+                return Err(DecompressError::ChecksumMismatch {
+                    context: ErrorContext::default(),
+                }
+                .into());
+            }
+        }
+        Ok(n)
+    }
+}
+
+fn truncated() -> DecompressError {
+    DecompressError::Truncated {
+        context: ErrorContext::default(),
+    }
+}
+
+fn read_link_target<R: Read>(
+    out: &mut CpioReader<R>,
+    header: &Header,
+) -> Result<PathBuf, DecompressError> {
+    if header.size > MAX_LINK_TARGET {
+        return Err(DecompressError::corrupt(format!(
+            "symlink target too long: `{}`",
+            header.path.display()
+        )));
+    }
+    let mut target = vec![];
+    out.read_to_end(&mut target)?;
+    Ok(bytes_path(&target))
+}
+
+/// Names and symlink targets are raw bytes, kept as is on unix like `ar` member names
+fn bytes_path(bytes: &[u8]) -> PathBuf {
+    #[cfg(windows)]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).to_string())
+    }
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::prelude::OsStrExt;
+        PathBuf::from(OsStr::from_bytes(bytes))
+    }
+}
+
+pub fn cpio_list<R: Read>(out: &mut CpioReader<R>) -> Result<Vec<Entry>, DecompressError> {
+    let mut entries = vec![];
+    // hardlinked entries without data link to the one holding it
+    let mut links: HashMap<(u64, u64, u64), Vec<usize>> = HashMap::new();
+    while let Some(header) = out.next_header()? {
+        let link_target = if header.kind() == EntryKind::Symlink {
+            Some(read_link_target(out, &header)?)
+        } else {
+            None
+        };
+        if let Some(key) = header.link_key() {
+            links.entry(key).or_default().push(entries.len());
+        }
+        entries.push(Entry {
+            kind: header.kind(),
+            size: Some(header.size),
+            mode: Some(header.permissions()),
+            mtime: Some(header.mtime),
+            link_target,
+            uid: Some(header.uid),
+            gid: Some(header.gid),
+            path: header.path,
+            ..Entry::default()
+        });
+    }
+    for indices in links.into_values() {
+        let data = indices
+            .iter()
+            .copied()
+            .find(|i| entries[*i].size != Some(0))
+            .unwrap_or(indices[0]);
+        let target = entries[data].path.clone();
+        for i in indices.into_iter().filter(|i| *i != data) {
+            entries[i].kind = EntryKind::Hardlink;
+            entries[i].link_target = Some(target.clone());
+        }
+    }
+    Ok(entries)
+}
+
+/// Hardlinked entries seen so far, by inode
+#[derive(Default)]
+struct HardLinks {
+    /// Output path of the entry that holds the data
+    written: HashMap<(u64, u64, u64), PathBuf>,
+    /// Entries waiting for the one that holds the data
+    waiting: HashMap<(u64, u64, u64), Waiting>,
+}
+
+/// The entries of an inode waiting for its data
+#[derive(Default)]
+struct Waiting {
+    /// Output paths of the entries to extract, with their mtime
+    links: Vec<(PathBuf, i64)>,
+    /// Entries of the inode seen, extracted or not
+    seen: u64,
+    nlink: u64,
+    mode: u32,
+}
+
+impl Waiting {
+    fn see(&mut self, header: &Header) {
+        self.seen += 1;
+        self.nlink = header.nlink;
+        self.mode = header.permissions();
+    }
+}
+
+/// The state of an extraction into `to`
+struct Extraction<'a> {
+    to: &'a Path,
+    opts: &'a ExtractOpts,
+    budget: &'a mut Budget,
+    report: Decompression,
+    copies: Copies,
+    pending: PendingDirs,
+    hardlinks: HardLinks,
+}
+
+pub fn cpio_extract<R: Read>(
+    out: &mut CpioReader<R>,
+    to: &Path,
+    opts: &ExtractOpts,
+    budget: &mut Budget,
+) -> Result<Decompression, DecompressError> {
+    if !to.exists() {
+        fs::create_dir_all(to)?;
+    }
+    let mut ex = Extraction {
+        to,
+        opts,
+        budget,
+        report: Decompression::default(),
+        copies: Copies::default(),
+        pending: PendingDirs::default(),
+        hardlinks: HardLinks::default(),
+    };
+    while let Some(header) = out.next_header()? {
+        let res = ex
+            .extract_entry(out, &header)
+            .map_err(|err| err.with_entry(&header.path));
+        skip_unsafe(res, &mut ex.report, opts)?;
+    }
+    ex.finish()
+}
+
+impl<'a> Extraction<'a> {
+    fn extract_entry<R: Read>(
+        &mut self,
+        out: &mut CpioReader<R>,
+        header: &Header,
+    ) -> Result<(), DecompressError> {
+        let (to, opts) = (self.to, self.opts);
+        // strip prefixed components, like for tar
+        let filepath = header
+            .path
+            .components()
+            .skip(opts.strip)
+            .collect::<PathBuf>();
+        let outpath = safe_join(to, &filepath)?;
+        if to == outpath {
+            return self.left_out(out, header);
+        }
+
+        if !(opts.filter)(outpath.as_path()) {
+            self.report
+                .filtered
+                .push(outpath.to_string_lossy().to_string());
+            return self.left_out(out, header);
+        }
+
+        let outpath = (opts.map)(outpath.as_path()).into_owned();
+        ensure_contained(to, &outpath)?;
+        self.budget.entry(to, &outpath)?;
+
+        let times = EntryTimes::mtime(header.mtime);
+        match header.kind() {
+            EntryKind::Directory => {
+                prepare_outpath(to, &outpath)?;
+                fs::create_dir_all(&outpath)?;
+                self.pending
+                    .push(&outpath, Some(header.permissions()), times);
+                self.report.dirs.push(outpath.to_string_lossy().to_string());
+            }
+            EntryKind::Symlink => {
+                let link = Link::Symbolic(read_link_target(out, header)?);
+                self.link(&outpath, link, header.mtime)?;
+            }
+            EntryKind::File => {
+                let key = header.link_key();
+                if let Some(key) = key {
+                    if let Some(target) = self.hardlinks.written.get(&key) {
+                        // the data came with an earlier entry
+                        let link = Link::Hard(target.clone());
+                        return self.link(&outpath, link, header.mtime);
+                    }
+                    if header.size == 0 {
+                        let waiting = self.hardlinks.waiting.entry(key).or_default();
+                        waiting.see(header);
+                        waiting.links.push((outpath, header.mtime));
+                        return Ok(());
+                    }
+                }
+
+                let mode = normalize_mode(header.permissions());
+                let written = self.write_file(out, &outpath, header.mtime, mode)?;
+                if let Some(key) = key {
+                    let links = self
+                        .hardlinks
+                        .waiting
+                        .remove(&key)
+                        .unwrap_or_default()
+                        .links;
+                    self.write_links(key, written.or_else(|| kept(&outpath)), links)?;
+                }
+            }
+            EntryKind::Hardlink | EntryKind::Other => {
+                // device nodes, fifos and sockets are not recreated
+                self.report
+                    .skipped
+                    .push(outpath.to_string_lossy().to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Account for an entry left out by `strip` or `filter`. The data of hardlinks left out
+    /// goes to the first of the links waiting for it.
+    fn left_out<R: Read>(
+        &mut self,
+        out: &mut CpioReader<R>,
+        header: &Header,
+    ) -> Result<(), DecompressError> {
+        let Some(key) = header.link_key() else {
+            return Ok(());
+        };
+        if self.hardlinks.written.contains_key(&key) {
+            return Ok(());
+        }
+        let waiting = self.hardlinks.waiting.entry(key).or_default();
+        waiting.see(header);
+        if header.size == 0 || waiting.links.is_empty() {
+            return Ok(());
+        }
+        let mut links = std::mem::take(&mut waiting.links);
+        let (first, mtime) = links.remove(0);
+        self.hardlinks.waiting.remove(&key);
+        let mode = normalize_mode(header.permissions());
+        let written = self.write_file(out, &first, mtime, mode)?;
+        self.write_links(key, written.or_else(|| kept(&first)), links)
+    }
+
+    /// Extract the `links` of an inode to `target`, the file holding its data. They are
+    /// skipped when there is none, unless the policy forbids links.
+    fn write_links(
+        &mut self,
+        key: (u64, u64, u64),
+        target: Option<PathBuf>,
+        links: Vec<(PathBuf, i64)>,
+    ) -> Result<(), DecompressError> {
+        for (outpath, mtime) in links {
+            let res = extract_hardlink(
+                self.to,
+                &outpath,
+                target.clone(),
+                Some(mtime),
+                self.opts,
+                &mut self.copies,
+                &mut self.report,
+            );
+            skip_unsafe(res, &mut self.report, self.opts)?;
+        }
+        if let Some(target) = target {
+            self.hardlinks.written.insert(key, target);
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, outpath: &Path, link: Link, mtime: i64) -> Result<(), DecompressError> {
+        extract_link(
+            self.to,
+            outpath,
+            link,
+            Some(mtime),
+            self.opts,
+            &mut self.copies,
+            &mut self.report,
+        )
+    }
+
+    /// Write a file entry, returning where it was written unless `ExtractOpts.overwrite`
+    /// left it out
+    fn write_file(
+        &mut self,
+        data: &mut impl Read,
+        outpath: &Path,
+        mtime: i64,
+        _mode: u32,
+    ) -> Result<Option<PathBuf>, DecompressError> {
+        let outpath = match resolve_existing(outpath, Some(mtime), self.opts, &mut self.report)? {
+            Some(outpath) => outpath,
+            None => return Ok(None),
+        };
+        prepare_outpath(self.to, &outpath)?;
+        let mut outfile = fs::File::create(&outpath)?;
+        self.report.bytes_written += self.budget.copy(data, &mut outfile)?;
+        self.report
+            .files
+            .push(outpath.to_string_lossy().to_string());
+        set_times(&outpath, EntryTimes::mtime(mtime), self.opts)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&outpath, fs::Permissions::from_mode(_mode))?;
+        }
+        Ok(Some(outpath))
+    }
+
+    fn finish(mut self) -> Result<Decompression, DecompressError> {
+        // hardlinked entries that never got data are empty files when all of the inode's
+        // entries were seen, otherwise their data was not in the archive and they are skipped
+        let waiting = std::mem::take(&mut self.hardlinks.waiting);
+        for (key, mut waiting) in waiting {
+            if waiting.links.is_empty() {
+                continue;
+            }
+            let target = if waiting.seen >= waiting.nlink {
+                let (first, mtime) = waiting.links.remove(0);
+                let mode = normalize_mode(waiting.mode);
+                let mut target = None;
+                let res = self
+                    .write_file(&mut io::empty(), &first, mtime, mode)
+                    .map(|written| target = written.or_else(|| kept(&first)));
+                skip_unsafe(res, &mut self.report, self.opts)?;
+                target
+            } else {
+                None
+            };
+            self.write_links(key, target, waiting.links)?;
+        }
+        self.copies
+            .finish(self.to, self.opts, &mut self.report, self.budget)?;
+        self.pending.finish(self.opts)?;
+        Ok(self.report)
+    }
+}
+
+/// The file left in place of an entry by `ExtractOpts.overwrite`, which links then point to
+fn kept(outpath: &Path) -> Option<PathBuf> {
+    fs::symlink_metadata(outpath)
+        .is_ok_and(|meta| meta.is_file())
+        .then(|| outpath.to_path_buf())
+}
@@ -0,0 +1,156 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::decompressors::codecs;
+use crate::decompressors::limits::Budget;
+use crate::decompressors::tar_common::{tar_extract, tar_list};
+use crate::decompressors::utils::{listing, measure, merge_report};
+use crate::{DecompressError, Decompression, Decompressor, ExtractOpts, Listing};
+
+lazy_static! {
+    static ref RE: Regex = Regex::new(r"(?i)\.deb$").unwrap();
+}
+
+/// Debian packages: an `ar` archive holding `debian-binary`, a `control.tar.*` and a
+/// `data.tar.*`. The data payload is extracted and listed, control files (`control`,
+/// maintainer scripts) are extracted only when a folder is given for them.
+#[derive(Default)]
+pub struct Deb {
+    re: Option<Regex>,
+    control: Option<PathBuf>,
+}
+
+impl Deb {
+    #[must_use]
+    pub fn new(re: Option<Regex>) -> Self {
+        Self { re, control: None }
+    }
+    #[must_use]
+    pub fn build(re: Option<Regex>) -> Box<Self> {
+        Box::new(Self::new(re))
+    }
+
+    /// Also extract control files, into `to`. They are extracted with the same options as
+    /// the data payload, and reported along with it.
+    #[must_use]
+    pub fn with_control(mut self, to: impl Into<PathBuf>) -> Self {
+        self.control = Some(to.into());
+        self
+    }
+}
+
+impl Decompressor for Deb {
+    fn test_mimetype(&self, archive: &str) -> bool {
+        archive == "application/vnd.debian.binary-package"
+    }
+
+    fn test(&self, archive: &Path) -> bool {
+        archive
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .is_some_and(|f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
+    }
+
+    fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
+        listing("deb", || {
+            let mut out = ar::Archive::new(BufReader::new(File::open(archive)?));
+            while let Some(entry) = out.next_entry() {
+                let entry = entry?;
+                let name = String::from_utf8_lossy(entry.header().identifier()).to_string();
+                if name.starts_with("data.tar") {
+                    return Ok(Listing {
+                        id: "deb",
+                        entries: tar_list(&mut tar::Archive::new(decoder(&name, entry)?))?,
+                        package: None,
+                    });
+                }
+            }
+            Err(DecompressError::corrupt("package has no data.tar"))
+        })
+    }
+
+    fn decompress(
+        &self,
+        archive: &Path,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        self.decompress_reader(Box::new(File::open(archive)?), to, opts)
+    }
+
+    fn decompress_reader(
+        &self,
+        reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        measure("deb", reader, opts, |reader, mut budget| {
+            self.deb_extract(reader, to, opts, &mut budget)
+        })
+    }
+}
+
+impl Deb {
+    fn deb_extract(
+        &self,
+        reader: impl Read,
+        to: &Path,
+        opts: &ExtractOpts,
+        budget: &mut Budget,
+    ) -> Result<Decompression, DecompressError> {
+        let mut report = Decompression::default();
+        let mut has_data = false;
+        let mut out = ar::Archive::new(BufReader::new(reader));
+        while let Some(entry) = out.next_entry() {
+            let mut entry = entry?;
+            let name = String::from_utf8_lossy(entry.header().identifier()).to_string();
+            if name == "debian-binary" {
+                let mut version = String::new();
+                entry.read_to_string(&mut version)?;
+                if !version.starts_with("2.") {
+                    return Err(DecompressError::unsupported(format!(
+                        "package format: `{}`",
+                        version.trim()
+                    )));
+                }
+            } else if name.starts_with("control.tar") {
+                if let Some(control) = &self.control {
+                    let mut tar = tar::Archive::new(decoder(&name, entry)?);
+                    merge_report(&mut report, tar_extract(&mut tar, control, opts, budget)?);
+                }
+            } else if name.starts_with("data.tar") {
+                let mut tar = tar::Archive::new(decoder(&name, entry)?);
+                merge_report(&mut report, tar_extract(&mut tar, to, opts, budget)?);
+                has_data = true;
+            }
+        }
+        if has_data {
+            Ok(report)
+        } else {
+            Err(DecompressError::corrupt("package has no data.tar"))
+        }
+    }
+}
+
+/// Decode a member tarball, given its name (`data.tar.xz`), with the codec of its extension
+fn decoder<'a>(name: &str, member: impl Read + 'a) -> Result<Box<dyn Read + 'a>, DecompressError> {
+    let rdr = BufReader::new(member);
+    match Path::new(name)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+    {
+        Some("tar") => Ok(Box::new(rdr)),
+        Some(ext) => codecs::by_extension(ext)
+            .ok_or_else(|| DecompressError::unsupported(format!("package member: `{name}`")))?
+            .decoder(Box::new(rdr)),
+        None => Err(DecompressError::unsupported(format!(
+            "package member: `{name}`"
+        ))),
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::decompressors::utils::ReadCount;
+use crate::{DecompressError, ExtractOpts, Limit, Limits, Spent};
+
+/// Tracks an extraction against `ExtractOpts.limits`
+pub struct Budget {
+    limits: Limits,
+    read: ReadCount,
+    written: u64,
+    entries: usize,
+    /// What the extractions this one is nested within wrote
+    spent: Spent,
+}
+
+impl Budget {
+    /// A budget for an extraction, `read` follows the bytes read from the archive
+    #[must_use]
+    pub fn new(limits: Limits, read: ReadCount) -> Self {
+        Self {
+            limits,
+            read,
+            written: 0,
+            entries: 0,
+            spent: Spent::default(),
+        }
+    }
+
+    /// A budget for an extraction with `opts`, which counts towards `Limits.total_size` and
+    /// `Nested.total_size` what the extractions it is nested within wrote
+    #[must_use]
+    pub(crate) fn for_opts(opts: &ExtractOpts, read: ReadCount) -> Self {
+        Self {
+            spent: opts.spent,
+            ..Self::new(opts.limits, read)
+        }
+    }
+
+    /// Account for an entry about to be extracted to `outpath`
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecompressError::LimitExceeded` if there are too many entries, or the path is
+    /// too deep
+    pub fn entry(&mut self, to: &Path, outpath: &Path) -> Result<(), DecompressError> {
+        self.entries += 1;
+        if let Some(max) = self.limits.entries.filter(|max| self.entries > *max) {
+            return Err(DecompressError::limit_exceeded(Limit::Entries(max)));
+        }
+        let depth = outpath
+            .strip_prefix(to)
+            .map_or(0, |rest| rest.components().count());
+        if let Some(max) = self.limits.depth.filter(|max| depth > *max) {
+            return Err(DecompressError::limit_exceeded(Limit::Depth(max)));
+        }
+        Ok(())
+    }
+
+    /// Account for `n` bytes more of an entry of which `entry_written` bytes were written
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecompressError::LimitExceeded` if the entry or the extraction grows too big
+    pub fn add(&mut self, entry_written: u64, n: u64) -> Result<(), DecompressError> {
+        let limits = &self.limits;
+        self.written += n;
+        if let Some(max) = limits.entry_size.filter(|max| entry_written + n > *max) {
+            return Err(DecompressError::limit_exceeded(Limit::EntrySize(max)));
+        }
+        if let Some(max) = limits
+            .total_size
+            .filter(|max| self.spent.total + self.written > *max)
+        {
+            return Err(DecompressError::limit_exceeded(Limit::TotalSize(max)));
+        }
+        if let Some(max) = self
+            .spent
+            .nested_size
+            .filter(|max| self.spent.nested + self.written > *max)
+        {
+            return Err(DecompressError::limit_exceeded(Limit::NestedSize(max)));
+        }
+        if let Some(max) = limits
+            .ratio
+            .filter(|max| self.written > self.read.get().saturating_mul(*max))
+        {
+            return Err(DecompressError::limit_exceeded(Limit::Ratio(max)));
+        }
+        Ok(())
+    }
+
+    /// Copy the content of an entry, failing as soon as a limit is exceeded,
+    /// returns the bytes written
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecompressError::LimitExceeded` when exceeding a limit, or an error on IO failure
+    pub fn copy(
+        &mut self,
+        reader: &mut (impl Read + ?Sized),
+        writer: &mut impl Write,
+    ) -> Result<u64, DecompressError> {
+        let mut buf = [0; 8192];
+        let mut entry_written = 0;
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(entry_written),
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            self.add(entry_written, n as u64)?;
+            writer.write_all(&buf[..n])?;
+            entry_written += n as u64;
+        }
+    }
+}
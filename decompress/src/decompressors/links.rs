@@ -0,0 +1,229 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::decompressors::limits::Budget;
+#[cfg(any(feature = "tarball", feature = "rpm", feature = "cpio"))]
+use crate::decompressors::utils::ensure_no_symlink_escape;
+use crate::decompressors::utils::{prepare_outpath, resolve_existing, skip_unsafe};
+use crate::{DecompressError, Decompression, ExtractOpts, LinkPolicy};
+
+pub enum Link {
+    /// A symlink, with its target as recorded in the archive
+    Symbolic(PathBuf),
+    /// A hardlink, with the output path of the entry it links to
+    #[cfg(any(feature = "tarball", feature = "rpm", feature = "cpio"))]
+    Hard(PathBuf),
+}
+
+/// Links that are extracted as copies of their targets. Copies are made once all entries
+/// are extracted, because a symlink may point at an entry that comes later in the archive.
+#[derive(Default)]
+pub struct Copies {
+    pending: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Copies {
+    /// Copy all link targets into place, recording the paths written in `report`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a target is missing, resolves outside of `to`, or on IO failure
+    pub fn finish(
+        self,
+        to: &Path,
+        opts: &ExtractOpts,
+        report: &mut Decompression,
+        budget: &mut Budget,
+    ) -> Result<(), DecompressError> {
+        let root = to.canonicalize()?;
+        for (from, outpath) in self.pending {
+            let res = copy_link(to, &root, &from, &outpath, report, budget);
+            skip_unsafe(res, report, opts)?;
+        }
+        Ok(())
+    }
+}
+
+fn copy_link(
+    to: &Path,
+    root: &Path,
+    from: &Path,
+    outpath: &Path,
+    report: &mut Decompression,
+    budget: &mut Budget,
+) -> Result<(), DecompressError> {
+    prepare_outpath(to, outpath)?;
+    report.bytes_written += copy_all(to, root, from, outpath, budget)?;
+    report.files.push(outpath.to_string_lossy().to_string());
+    Ok(())
+}
+
+/// Extract a link entry according to `ExtractOpts.links`, recording the outcome in `report`.
+/// An existing file in its place is handled like for any other entry, with the link `mtime`.
+///
+/// # Errors
+///
+/// Returns an error if the policy forbids links, the link is unsafe, or on IO failure
+pub fn extract_link(
+    to: &Path,
+    outpath: &Path,
+    link: Link,
+    mtime: Option<i64>,
+    opts: &ExtractOpts,
+    copies: &mut Copies,
+    report: &mut Decompression,
+) -> Result<(), DecompressError> {
+    match opts.links {
+        LinkPolicy::Skip => {
+            report.skipped.push(outpath.to_string_lossy().to_string());
+            Ok(())
+        }
+        // the entry is in the context of the error
+        LinkPolicy::Error => Err(links_forbidden()),
+        LinkPolicy::Copy => {
+            let outpath = match resolve_existing(outpath, mtime, opts, report)? {
+                Some(outpath) => outpath,
+                None => return Ok(()),
+            };
+            let from = match link {
+                Link::Symbolic(target) => resolve_symlink(to, &outpath, &target)?,
+                #[cfg(any(feature = "tarball", feature = "rpm", feature = "cpio"))]
+                Link::Hard(target) => target,
+            };
+            copies.pending.push((from, outpath));
+            Ok(())
+        }
+        LinkPolicy::Create => {
+            let outpath = match resolve_existing(outpath, mtime, opts, report)? {
+                Some(outpath) => outpath,
+                None => return Ok(()),
+            };
+            prepare_outpath(to, &outpath)?;
+            if fs::symlink_metadata(&outpath).is_ok() {
+                fs::remove_file(&outpath)?;
+            }
+            match link {
+                Link::Symbolic(target) => symlink(&target, &outpath)?,
+                #[cfg(any(feature = "tarball", feature = "rpm", feature = "cpio"))]
+                Link::Hard(target) => {
+                    ensure_no_symlink_escape(to, &target)?;
+                    fs::hard_link(target, &outpath)?;
+                }
+            }
+            report.links.push(outpath.to_string_lossy().to_string());
+            Ok(())
+        }
+    }
+}
+
+/// Extract a hardlink to `target`, the output path of the entry it links to, like
+/// `extract_link`. A target that was not extracted leaves nothing to link to, the link is
+/// skipped unless the policy forbids links.
+///
+/// # Errors
+///
+/// Returns an error if the policy forbids links, the link is unsafe, or on IO failure
+#[cfg(any(feature = "tarball", feature = "rpm", feature = "cpio"))]
+pub fn extract_hardlink(
+    to: &Path,
+    outpath: &Path,
+    target: Option<PathBuf>,
+    mtime: Option<i64>,
+    opts: &ExtractOpts,
+    copies: &mut Copies,
+    report: &mut Decompression,
+) -> Result<(), DecompressError> {
+    match target {
+        Some(target) => extract_link(to, outpath, Link::Hard(target), mtime, opts, copies, report),
+        None if opts.links == LinkPolicy::Error => Err(links_forbidden()),
+        None => {
+            report.skipped.push(outpath.to_string_lossy().to_string());
+            Ok(())
+        }
+    }
+}
+
+fn links_forbidden() -> DecompressError {
+    DecompressError::unsupported("links, by `LinkPolicy::Error`")
+}
+
+/// Resolve a symlink target lexically, relative to the link. Absolute targets and targets
+/// leaving `to` are rejected, as their content comes from outside of the archive.
+fn resolve_symlink(to: &Path, link: &Path, target: &Path) -> Result<PathBuf, DecompressError> {
+    let unsafe_path = || DecompressError::unsafe_path(target.to_path_buf());
+    let base = link
+        .parent()
+        .and_then(|p| p.strip_prefix(to).ok())
+        .ok_or_else(unsafe_path)?;
+
+    let mut resolved = PathBuf::new();
+    for component in base.join(target).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return Err(unsafe_path());
+                }
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(..) => return Err(unsafe_path()),
+        }
+    }
+    Ok(to.join(resolved))
+}
+
+/// Copy a file or a folder recursively, returning the bytes written. Copies count against
+/// the limits like any other content, a link to a folder can copy a lot.
+/// Targets are resolved on disk, which also catches links already there: a target must be
+/// in `root`, the canonical `to`, and must not hold its copy (`a/loop -> .`).
+fn copy_all(
+    to: &Path,
+    root: &Path,
+    from: &Path,
+    outpath: &Path,
+    budget: &mut Budget,
+) -> Result<u64, DecompressError> {
+    let from = from.canonicalize()?;
+    if !from.starts_with(root) {
+        return Err(DecompressError::unsafe_path(from));
+    }
+    let resolved = match (outpath.parent(), outpath.file_name()) {
+        (Some(parent), Some(name)) => parent.canonicalize()?.join(name),
+        _ => return Err(DecompressError::unsafe_path(outpath.to_path_buf())),
+    };
+    if resolved.starts_with(&from) {
+        return Err(DecompressError::unsafe_path(outpath.to_path_buf()));
+    }
+
+    if from.is_dir() {
+        fs::create_dir_all(outpath)?;
+        let mut written = 0;
+        for entry in fs::read_dir(&from)? {
+            let entry = entry?;
+            let outpath = outpath.join(entry.file_name());
+            budget.entry(to, &outpath)?;
+            written += copy_all(to, root, &entry.path(), &outpath, budget)?;
+        }
+        Ok(written)
+    } else {
+        let written = budget.copy(&mut fs::File::open(&from)?, &mut fs::File::create(outpath)?)?;
+        fs::set_permissions(outpath, fs::metadata(&from)?.permissions())?;
+        Ok(written)
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    if link.parent().is_some_and(|p| p.join(target).is_dir()) {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
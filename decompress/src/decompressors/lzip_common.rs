@@ -0,0 +1,153 @@
+use std::io::{self, BufRead, Read};
+
+use xz::stream::{Action, Status, Stream};
+
+use crate::{DecompressError, ErrorContext};
+
+const MAGIC: &[u8] = b"LZIP";
+
+/// Literal context bits, literal position bits and position bits lzip always uses
+const LZMA_PROPERTIES: u8 = 0x5d;
+
+/// The dictionary sizes lzip allows, a header is not trusted with more, as the decoder
+/// allocates the dictionary up front
+const DICT_SIZES: std::ops::RangeInclusive<u32> = (1 << 12)..=(1 << 29);
+
+/// What the decoder may allocate: the largest dictionary and the decoder state
+const MEMLIMIT: u64 = (1 << 29) + (1 << 20);
+
+/// Decodes an lzip stream: one or more members, each an LZMA stream with an end marker,
+/// followed by a trailer with the CRC32 and size of its data.
+pub struct LzipDecoder<R> {
+    inner: R,
+    /// The decoder of the member in progress
+    stream: Option<Stream>,
+    /// The LZMA-alone header still to be fed to a new member's decoder
+    header: Vec<u8>,
+    crc: crc32fast::Hasher,
+    size: u64,
+    members: usize,
+}
+
+impl<R: BufRead> LzipDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            stream: None,
+            header: Vec::new(),
+            crc: crc32fast::Hasher::new(),
+            size: 0,
+            members: 0,
+        }
+    }
+
+    /// Start the next member, `None` at the end of the stream
+    fn next_member(&mut self) -> io::Result<Option<Stream>> {
+        if self.inner.fill_buf()?.is_empty() {
+            return if self.members == 0 {
+                Err(invalid("empty lzip stream"))
+            } else {
+                Ok(None)
+            };
+        }
+        let mut header = [0u8; 6];
+        self.inner.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != 1 {
+            return Err(invalid("malformed lzip member"));
+        }
+
+        // the dictionary size is a power of two, less up to 7 sixteenths of it
+        let base = 1u32
+            .checked_shl(u32::from(header[5] & 0x1f))
+            .ok_or_else(|| invalid("malformed lzip member"))?;
+        let dict_size = base - (base / 16) * u32::from(header[5] >> 5);
+        if !DICT_SIZES.contains(&dict_size) {
+            return Err(invalid("lzip dictionary size out of range"));
+        }
+
+        // an lzip member is an LZMA stream of unknown size, which is what the LZMA-alone
+        // header describes with an all ones size
+        let mut alone = vec![LZMA_PROPERTIES];
+        alone.extend_from_slice(&dict_size.to_le_bytes());
+        alone.extend_from_slice(&u64::MAX.to_le_bytes());
+        self.header = alone;
+        let stream = Stream::new_lzma_decoder(MEMLIMIT).map_err(io::Error::from)?;
+
+        self.members += 1;
+        self.crc = crc32fast::Hasher::new();
+        self.size = 0;
+        Ok(Some(stream))
+    }
+
+    /// Check the trailer of a member against what was decoded
+    fn finish_member(&mut self) -> io::Result<()> {
+        let mut trailer = [0u8; 20];
+        self.inner.read_exact(&mut trailer)?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&trailer[4..12]);
+        let crc_ok = crc == std::mem::take(&mut self.crc).finalize();
+        if !crc_ok || u64::from_le_bytes(size) != self.size {
+            return Err(DecompressError::ChecksumMismatch {
+                context: ErrorContext::default(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for LzipDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let Some(stream) = &mut self.stream else {
+                match self.next_member()? {
+                    Some(stream) => self.stream = Some(stream),
+                    None => return Ok(0),
+                }
+                continue;
+            };
+
+            let from_header = !self.header.is_empty();
+            let input = if from_header {
+                &self.header[..]
+            } else {
+                self.inner.fill_buf()?
+            };
+            let eof = input.is_empty();
+            let (before_in, before_out) = (stream.total_in(), stream.total_out());
+            let status = stream
+                .process(input, buf, Action::Run)
+                .map_err(io::Error::from)?;
+            let consumed = usize::try_from(stream.total_in() - before_in).unwrap_or(usize::MAX);
+            let read = usize::try_from(stream.total_out() - before_out).unwrap_or(usize::MAX);
+            if from_header {
+                self.header.drain(..consumed);
+            } else {
+                self.inner.consume(consumed);
+            }
+            self.crc.update(&buf[..read]);
+            self.size += read as u64;
+
+            if status == Status::StreamEnd {
+                self.stream = None;
+                self.finish_member()?;
+            } else if eof && read == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated lzip member",
+                ));
+            }
+            if read > 0 {
+                return Ok(read);
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
@@ -0,0 +1,207 @@
+use std::io::{self, BufRead, Read};
+
+use crate::DecompressError;
+
+const MAGIC: [u8; 2] = [0x1f, 0x9d];
+
+/// Set in the header when code 256 clears the table
+const BLOCK_MODE: u8 = 0x80;
+const MAX_BITS_MASK: u8 = 0x1f;
+
+const INIT_BITS: u32 = 9;
+const MAX_BITS: u32 = 16;
+const CLEAR: u32 = 256;
+
+/// Decodes the LZW stream of `compress(1)` (`.Z`).
+///
+/// Codes start at 9 bits and widen up to the maximum given in the header. They are written in
+/// groups of 8, so when the width changes or the table is cleared, the rest of the group is
+/// padding to skip.
+pub struct LzwDecoder<R> {
+    inner: R,
+    max_bits: u32,
+    block_mode: bool,
+    started: bool,
+
+    /// Pending input bits, least significant first
+    bits: u64,
+    nbits: u32,
+    /// Bits consumed since the code width last changed, to find the end of a group
+    group_bits: u64,
+
+    code_bits: u32,
+    max_code: u32,
+    free_ent: u32,
+    old_code: Option<u32>,
+    fin_char: u8,
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+
+    /// Decoded bytes not yet returned, in reverse order
+    stack: Vec<u8>,
+    done: bool,
+}
+
+impl<R: BufRead> LzwDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            max_bits: MAX_BITS,
+            block_mode: true,
+            started: false,
+            bits: 0,
+            nbits: 0,
+            group_bits: 0,
+            code_bits: INIT_BITS,
+            max_code: (1 << INIT_BITS) - 1,
+            free_ent: CLEAR,
+            old_code: None,
+            fin_char: 0,
+            prefix: vec![0; 1 << MAX_BITS],
+            suffix: (0..1u32 << MAX_BITS).map(|c| c as u8).collect(),
+            stack: Vec::new(),
+            done: false,
+        }
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let mut header = [0u8; 3];
+        self.inner.read_exact(&mut header)?;
+        if header[..2] != MAGIC {
+            return Err(invalid("not a compress stream"));
+        }
+        self.max_bits = u32::from(header[2] & MAX_BITS_MASK);
+        if !(INIT_BITS..=MAX_BITS).contains(&self.max_bits) {
+            return Err(DecompressError::unsupported("compress code width").into());
+        }
+        self.block_mode = header[2] & BLOCK_MODE != 0;
+        self.free_ent = if self.block_mode { CLEAR + 1 } else { CLEAR };
+        self.started = true;
+        Ok(())
+    }
+
+    /// Buffer at least `n` bits, false at the end of the stream
+    fn fill(&mut self, n: u32) -> io::Result<bool> {
+        while self.nbits < n {
+            let buf = self.inner.fill_buf()?;
+            let Some(&byte) = buf.first() else {
+                return Ok(false);
+            };
+            self.inner.consume(1);
+            self.bits |= u64::from(byte) << self.nbits;
+            self.nbits += 8;
+        }
+        Ok(true)
+    }
+
+    fn next_code(&mut self) -> io::Result<Option<u32>> {
+        if !self.fill(self.code_bits)? {
+            // what is left is the padding of the last group
+            return Ok(None);
+        }
+        let code = (self.bits & ((1 << self.code_bits) - 1)) as u32;
+        self.bits >>= self.code_bits;
+        self.nbits -= self.code_bits;
+        self.group_bits += u64::from(self.code_bits);
+        Ok(Some(code))
+    }
+
+    /// Skip to the end of the current group of codes and start over with `bits` wide codes
+    fn set_code_bits(&mut self, bits: u32) -> io::Result<()> {
+        let group = u64::from(self.code_bits) * 8;
+        let mut skip = (group - self.group_bits % group) % group;
+        while skip > 0 {
+            let n = u32::try_from(skip.min(32)).unwrap_or(32);
+            if !self.fill(n)? {
+                self.bits = 0;
+                self.nbits = 0;
+                break;
+            }
+            self.bits >>= n;
+            self.nbits -= n;
+            skip -= u64::from(n);
+        }
+        self.group_bits = 0;
+        self.code_bits = bits;
+        self.max_code = if bits == self.max_bits {
+            1 << self.max_bits
+        } else {
+            (1 << bits) - 1
+        };
+        Ok(())
+    }
+
+    /// Decode the next code onto the stack, false at the end of the stream
+    fn decode(&mut self) -> io::Result<bool> {
+        if self.free_ent > self.max_code {
+            self.set_code_bits(self.code_bits + 1)?;
+        }
+        let Some(code) = self.next_code()? else {
+            return Ok(false);
+        };
+
+        let Some(old_code) = self.old_code else {
+            if code >= CLEAR {
+                return Err(invalid("corrupt compress stream"));
+            }
+            self.old_code = Some(code);
+            self.fin_char = code as u8;
+            self.stack.push(self.fin_char);
+            return Ok(true);
+        };
+
+        if code == CLEAR && self.block_mode {
+            // the next entry overwrites the clear code and is never used
+            self.free_ent = CLEAR;
+            self.set_code_bits(INIT_BITS)?;
+            return Ok(true);
+        }
+
+        let mut cur = code;
+        if code >= self.free_ent {
+            // the code being defined: the previous string and its own first byte
+            if code > self.free_ent {
+                return Err(invalid("corrupt compress stream"));
+            }
+            self.stack.push(self.fin_char);
+            cur = old_code;
+        }
+        while cur >= CLEAR {
+            self.stack.push(self.suffix[cur as usize]);
+            cur = u32::from(self.prefix[cur as usize]);
+        }
+        self.fin_char = cur as u8;
+        self.stack.push(self.fin_char);
+
+        if self.free_ent < 1 << self.max_bits {
+            self.prefix[self.free_ent as usize] = old_code as u16;
+            self.suffix[self.free_ent as usize] = self.fin_char;
+            self.free_ent += 1;
+        }
+        self.old_code = Some(code);
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Read for LzwDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.started {
+            self.read_header()?;
+        }
+        let mut n = 0;
+        while n < buf.len() {
+            if let Some(byte) = self.stack.pop() {
+                buf[n] = byte;
+                n += 1;
+            } else if self.done || !self.decode()? {
+                self.done = true;
+                break;
+            }
+        }
+        Ok(n)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
@@ -1,23 +1,71 @@
 #[cfg(feature = "tarball")]
 mod tar_common;
 
-#[cfg(feature = "tarball")]
-pub mod tarball;
+#[cfg(any(
+    feature = "tarball",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+pub mod codecs;
+#[cfg(any(
+    feature = "tarball",
+    feature = "cpio",
+    feature = "deb",
+    feature = "rpm",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+pub mod compose;
+#[cfg(any(feature = "tarball", feature = "cpio"))]
+pub mod containers;
 
-#[cfg(feature = "tarzst")]
-pub mod tarzst;
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz"
+))]
+mod links;
 
-#[cfg(feature = "tarxz")]
-pub mod tarxz;
+#[cfg(any(feature = "rpm", feature = "cpio"))]
+mod cpio_common;
 
-#[cfg(feature = "tarbz")]
-pub mod tarbz;
+#[cfg(any(
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+mod stream_common;
 
-#[cfg(feature = "gz")]
-pub mod gz;
+#[cfg(any(feature = "lz", feature = "tarlz"))]
+mod lzip_common;
 
-#[cfg(feature = "targz")]
-pub mod targz;
+#[cfg(any(feature = "z", feature = "tarz"))]
+mod lzw_common;
 
 #[cfg(feature = "zip")]
 pub mod zip;
@@ -25,16 +73,237 @@ pub mod zip;
 #[cfg(feature = "rar")]
 pub mod unrar;
 
+#[cfg(feature = "sevenz")]
+pub mod sevenz;
+
 #[cfg(feature = "ar")]
 pub mod ar;
 
-#[cfg(feature = "bz2")]
-pub mod bz2;
+#[cfg(feature = "deb")]
+pub mod deb;
 
-#[cfg(feature = "xz")]
-pub mod xz;
-
-#[cfg(feature = "zstd")]
-pub mod zstd;
+#[cfg(feature = "rpm")]
+pub mod rpm;
 
+// helpers for the decompressors, unused without any
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "rar",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+pub mod limits;
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "rar",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
 mod utils;
+
+/// A composition of a container and a codec, or a codec on its own, under a name of its own,
+/// a decompressor like the others for stacks built by hand
+macro_rules! composed {
+    ($feature:literal, $module:ident, $name:ident, $doc:literal, tar + $codec:ident) => {
+        composed!(
+            $feature,
+            $module,
+            $name,
+            $doc,
+            Composed,
+            |re| Composed::new(
+                Arc::new(crate::decompressors::containers::Tar),
+                Some(Arc::new(crate::decompressors::codecs::$codec)),
+                re
+            )
+        );
+    };
+    ($feature:literal, $module:ident, $name:ident, $doc:literal, $id:literal: $codec:ident) => {
+        composed!($feature, $module, $name, $doc, Stream, |re| Stream::new(
+            $id,
+            Arc::new(crate::decompressors::codecs::$codec),
+            re
+        ));
+    };
+    ($feature:literal, $module:ident, $name:ident, $doc:literal, $inner:ident, |$re:ident| $new:expr) => {
+        #[cfg(feature = $feature)]
+        pub mod $module {
+            use std::{io::Read, path::Path, sync::Arc};
+
+            use regex::Regex;
+
+            use crate::decompressors::compose::$inner;
+            use crate::{DecompressError, Decompression, Decompressor, ExtractOpts, Listing};
+
+            #[doc = $doc]
+            pub struct $name($inner);
+
+            impl Default for $name {
+                fn default() -> Self {
+                    Self::new(None)
+                }
+            }
+
+            impl $name {
+                #[must_use]
+                pub fn new($re: Option<Regex>) -> Self {
+                    Self($new)
+                }
+                #[must_use]
+                pub fn build(re: Option<Regex>) -> Box<Self> {
+                    Box::new(Self::new(re))
+                }
+            }
+
+            impl Decompressor for $name {
+                fn test_mimetype(&self, mimetype: &str) -> bool {
+                    self.0.test_mimetype(mimetype)
+                }
+
+                fn test_content(&self, content: &mut dyn Read) -> bool {
+                    self.0.test_content(content)
+                }
+
+                fn test(&self, archive: &Path) -> bool {
+                    self.0.test(archive)
+                }
+
+                fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
+                    self.0.list(archive)
+                }
+
+                fn decompress(
+                    &self,
+                    archive: &Path,
+                    to: &Path,
+                    opts: &ExtractOpts,
+                ) -> Result<Decompression, DecompressError> {
+                    self.0.decompress(archive, to, opts)
+                }
+
+                fn decompress_reader(
+                    &self,
+                    reader: Box<dyn Read>,
+                    to: &Path,
+                    opts: &ExtractOpts,
+                ) -> Result<Decompression, DecompressError> {
+                    self.0.decompress_reader(reader, to, opts)
+                }
+            }
+        }
+    };
+}
+
+composed!(
+    "tarball",
+    tarball,
+    Tarball,
+    "tar archives",
+    Composed,
+    |re| Composed::new(Arc::new(crate::decompressors::containers::Tar), None, re)
+);
+composed!(
+    "targz",
+    targz,
+    Targz,
+    "gzip compressed tar archives",
+    tar + Gzip
+);
+composed!(
+    "tarxz",
+    tarxz,
+    Tarxz,
+    "xz compressed tar archives",
+    tar + Xz
+);
+composed!(
+    "tarbz",
+    tarbz,
+    Tarbz,
+    "bzip2 compressed tar archives",
+    tar + Bzip2
+);
+composed!(
+    "tarzst",
+    tarzst,
+    Tarzst,
+    "zstd compressed tar archives",
+    tar + Zstd
+);
+composed!(
+    "tarlz4",
+    tarlz4,
+    Tarlz4,
+    "LZ4 compressed tar archives",
+    tar + Lz4
+);
+composed!(
+    "tarbr",
+    tarbr,
+    Tarbr,
+    "brotli compressed tar archives",
+    tar + Brotli
+);
+composed!(
+    "tarlz",
+    tarlz,
+    Tarlz,
+    "lzip compressed tar archives",
+    tar + Lzip
+);
+composed!(
+    "tarlzma",
+    tarlzma,
+    Tarlzma,
+    "LZMA-alone compressed tar archives",
+    tar + Lzma
+);
+composed!(
+    "tarz",
+    tarz,
+    Tarz,
+    "`compress(1)` compressed tar archives",
+    tar + Compress
+);
+composed!(
+    "cpio",
+    cpio,
+    Cpio,
+    "cpio archives, as is or compressed with a codec told apart by its magic bytes",
+    Composed,
+    |re| Composed::new(Arc::new(crate::decompressors::containers::Cpio), None, re)
+        .with_codecs(crate::decompressors::codecs::all())
+);
+composed!("gz", gz, Gz, "gzip compressed files", "gz": Gzip);
+composed!("bz2", bz2, Bz2, "bzip2 compressed files", "bz2": Bzip2);
+composed!("xz", xz, Xz, "xz compressed files", "xz": Xz);
+composed!("zstd", zstd, Zstd, "zstd compressed files", "zst": Zstd);
+composed!("lz4", lz4, Lz4, "LZ4 compressed files", "lz4": Lz4);
+composed!("br", br, Br, "brotli compressed files", "br": Brotli);
+composed!("lz", lz, Lz, "lzip compressed files", "lz": Lzip);
+composed!("lzma", lzma, Lzma, "LZMA-alone compressed files", "lzma": Lzma);
+composed!("z", z, Z, "`compress(1)` compressed files", "z": Compress);
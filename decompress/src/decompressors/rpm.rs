@@ -0,0 +1,216 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::decompressors::codecs;
+use crate::decompressors::cpio_common::{cpio_extract, cpio_list, CpioReader};
+use crate::decompressors::utils::{listing, measure};
+use crate::{DecompressError, Decompression, Decompressor, ExtractOpts, Listing, Package};
+
+lazy_static! {
+    static ref RE: Regex = Regex::new(r"(?i)\.rpm$").unwrap();
+}
+
+const LEAD_MAGIC: &[u8] = &[0xed, 0xab, 0xee, 0xdb];
+const HEADER_MAGIC: &[u8] = &[0x8e, 0xad, 0xe8, 0x01];
+
+const TAG_NAME: u32 = 1000;
+const TAG_VERSION: u32 = 1001;
+const TAG_RELEASE: u32 = 1002;
+const TAG_ARCH: u32 = 1022;
+const TAG_PAYLOADFORMAT: u32 = 1124;
+const TAG_PAYLOADCOMPRESSOR: u32 = 1125;
+
+const TYPE_STRING: u32 = 6;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18NSTRING: u32 = 9;
+
+/// Headers bigger than this are not package metadata
+const MAX_HEADER_SIZE: u32 = 256 * 1024 * 1024;
+
+/// RPM packages: a lead, a signature header and a header describing the package, followed
+/// by a compressed cpio payload holding the files
+#[derive(Default)]
+pub struct Rpm {
+    re: Option<Regex>,
+}
+
+impl Rpm {
+    #[must_use]
+    pub fn new(re: Option<Regex>) -> Self {
+        Self { re }
+    }
+    #[must_use]
+    pub fn build(re: Option<Regex>) -> Box<Self> {
+        Box::new(Self::new(re))
+    }
+}
+
+impl Decompressor for Rpm {
+    fn test_mimetype(&self, archive: &str) -> bool {
+        archive == "application/x-rpm"
+    }
+
+    fn test(&self, archive: &Path) -> bool {
+        archive
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .is_some_and(|f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
+    }
+
+    fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
+        listing("rpm", || {
+            let mut reader = BufReader::new(File::open(archive)?);
+            let header = read_package(&mut reader)?;
+            let mut out = CpioReader::new(payload(&header, reader)?);
+            Ok(Listing {
+                id: "rpm",
+                entries: cpio_list(&mut out)?,
+                package: Some(header.package()?),
+            })
+        })
+    }
+
+    fn decompress(
+        &self,
+        archive: &Path,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        self.decompress_reader(Box::new(File::open(archive)?), to, opts)
+    }
+
+    fn decompress_reader(
+        &self,
+        reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        measure("rpm", reader, opts, |reader, mut budget| {
+            let mut reader = BufReader::new(reader);
+            let header = read_package(&mut reader)?;
+            let mut out = CpioReader::new(payload(&header, reader)?);
+            cpio_extract(&mut out, to, opts, &mut budget)
+        })
+    }
+}
+
+/// An RPM header: an index of tags, pointing into a data store
+struct Header {
+    /// tag, type, offset and count of each index entry
+    index: Vec<(u32, u32, u32, u32)>,
+    store: Vec<u8>,
+}
+
+impl Header {
+    /// The value of a string tag, the first one for arrays and translated strings
+    fn string(&self, tag: u32) -> Option<String> {
+        let (_, kind, offset, _) = self.index.iter().find(|(t, ..)| *t == tag)?;
+        if ![TYPE_STRING, TYPE_STRING_ARRAY, TYPE_I18NSTRING].contains(kind) {
+            return None;
+        }
+        let value = self.store.get(usize::try_from(*offset).ok()?..)?;
+        let end = value.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&value[..end]).to_string())
+    }
+
+    fn package(&self) -> Result<Package, DecompressError> {
+        let required = |tag, name| {
+            self.string(tag)
+                .ok_or_else(|| DecompressError::corrupt(format!("package has no {name}")))
+        };
+        Ok(Package {
+            name: required(TAG_NAME, "name")?,
+            version: required(TAG_VERSION, "version")?,
+            release: self.string(TAG_RELEASE),
+            arch: self.string(TAG_ARCH),
+        })
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_header(reader: &mut impl Read) -> Result<Header, DecompressError> {
+    let mut intro = [0u8; 16];
+    reader.read_exact(&mut intro)?;
+    if &intro[..4] != HEADER_MAGIC {
+        return Err(DecompressError::corrupt("malformed rpm header"));
+    }
+    let count = be_u32(&intro[8..12]);
+    let size = be_u32(&intro[12..16]);
+    if count.saturating_mul(16).saturating_add(size) > MAX_HEADER_SIZE {
+        return Err(DecompressError::corrupt("rpm header too big"));
+    }
+
+    let mut index = vec![0u8; count as usize * 16];
+    reader.read_exact(&mut index)?;
+    let mut store = vec![0u8; size as usize];
+    reader.read_exact(&mut store)?;
+    Ok(Header {
+        index: index
+            .chunks_exact(16)
+            .map(|e| {
+                (
+                    be_u32(e),
+                    be_u32(&e[4..]),
+                    be_u32(&e[8..]),
+                    be_u32(&e[12..]),
+                )
+            })
+            .collect(),
+        store,
+    })
+}
+
+/// Read the lead, signature and header of a package, leaving `reader` at the payload
+fn read_package(reader: &mut impl Read) -> Result<Header, DecompressError> {
+    let mut lead = [0u8; 96];
+    reader.read_exact(&mut lead)?;
+    if &lead[..4] != LEAD_MAGIC {
+        return Err(DecompressError::corrupt("not an rpm package"));
+    }
+    if lead[4] < 3 {
+        return Err(DecompressError::unsupported(format!(
+            "rpm version `{}`",
+            lead[4]
+        )));
+    }
+
+    // the signature is padded to 8 bytes
+    let signature = read_header(reader)?;
+    let len = signature.index.len() * 16 + signature.store.len();
+    let mut pad = vec![0u8; (8 - len % 8) % 8];
+    reader.read_exact(&mut pad)?;
+
+    read_header(reader)
+}
+
+/// Decode the payload, following the compressor recorded in the header
+fn payload<'a>(
+    header: &Header,
+    reader: impl Read + 'a,
+) -> Result<Box<dyn Read + 'a>, DecompressError> {
+    let format = header.string(TAG_PAYLOADFORMAT);
+    if format.as_ref().is_some_and(|f| f != "cpio") {
+        return Err(DecompressError::unsupported(format!(
+            "rpm payload `{}`",
+            format.unwrap_or_default()
+        )));
+    }
+    // packages from before the tag was introduced are gzip compressed
+    let compressor = header
+        .string(TAG_PAYLOADCOMPRESSOR)
+        .unwrap_or_else(|| "gzip".to_string());
+    codecs::by_name(&compressor)
+        .ok_or_else(|| {
+            DecompressError::unsupported(format!("rpm payload compressor `{compressor}`"))
+        })?
+        .decoder(Box::new(BufReader::new(reader)))
+}
@@ -0,0 +1,306 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use sevenz_rust::{Password, SevenZArchiveEntry, SevenZReader};
+
+use crate::decompressors::limits::Budget;
+use crate::decompressors::links::{extract_link, Copies, Link};
+use crate::decompressors::utils::{
+    ensure_contained, listing, measure, normalize_mode, prepare_outpath, resolve_existing,
+    safe_join, set_times, skip_unsafe, EntryTimes, PendingDirs,
+};
+use crate::{
+    DecompressError, Decompression, Decompressor, Entry, EntryKind, ErrorContext, ExtractOpts,
+    Listing,
+};
+
+lazy_static! {
+    static ref RE: Regex = Regex::new(r"(?i)\.7z$").unwrap();
+}
+
+/// Set in the windows attributes when their high 16 bits hold a unix mode
+const UNIX_EXTENSION: u32 = 0x8000;
+const S_IFMT: u32 = 0o170_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// Symlink targets are stored as entry data, anything longer is not a path
+const MAX_LINK_TARGET: u64 = 4096;
+
+/// 7z archives, with the codecs of `sevenz-rust` (LZMA, LZMA2, BCJ filters, ...)
+#[derive(Default)]
+pub struct Sevenz {
+    re: Option<Regex>,
+}
+
+impl Sevenz {
+    #[must_use]
+    pub fn new(re: Option<Regex>) -> Self {
+        Self { re }
+    }
+    #[must_use]
+    pub fn build(re: Option<Regex>) -> Box<Self> {
+        Box::new(Self::new(re))
+    }
+}
+
+impl Decompressor for Sevenz {
+    fn test_mimetype(&self, archive: &str) -> bool {
+        archive == "application/x-7z-compressed"
+    }
+
+    fn test(&self, archive: &Path) -> bool {
+        archive
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .is_some_and(|f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
+    }
+
+    fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
+        listing("sevenz", || {
+            let out = sevenz_rust::Archive::open(archive).map_err(sevenz_error)?;
+            let entries = out
+                .files
+                .iter()
+                .filter(|entry| !entry.is_anti_item())
+                .map(|entry| Entry {
+                    path: entry_path(entry),
+                    kind: if entry.is_directory() {
+                        EntryKind::Directory
+                    } else if unix_mode(entry).is_some_and(|mode| mode & S_IFMT == S_IFLNK) {
+                        EntryKind::Symlink
+                    } else {
+                        EntryKind::File
+                    },
+                    size: Some(entry.size()),
+                    compressed_size: Some(entry.compressed_size).filter(|size| *size > 0),
+                    mode: unix_mode(entry).map(|mode| mode & 0o7777),
+                    mtime: entry_times(entry).mtime,
+                    crc: entry
+                        .has_crc
+                        .then(|| u32::try_from(entry.crc).ok())
+                        .flatten(),
+                    ..Entry::default()
+                })
+                .collect();
+            Ok(Listing {
+                id: "sevenz",
+                entries,
+                package: None,
+            })
+        })
+    }
+
+    fn decompress(
+        &self,
+        archive: &Path,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        let len = fs::metadata(archive)?.len();
+        measure("sevenz", File::open(archive)?, opts, |rdr, budget| {
+            let out = SevenZReader::new(rdr, len, Password::empty()).map_err(sevenz_error)?;
+            sevenz_extract(out, to, opts, budget)
+        })
+    }
+}
+
+fn sevenz_extract<R: Read + io::Seek>(
+    mut out: SevenZReader<R>,
+    to: &Path,
+    opts: &ExtractOpts,
+    mut budget: Budget,
+) -> Result<Decompression, DecompressError> {
+    let mut report = Decompression::default();
+    let mut copies = Copies::default();
+    let mut pending = PendingDirs::default();
+    if !to.exists() {
+        fs::create_dir_all(to)?;
+    }
+
+    // entries are decoded in a callback, the first failure stops decoding and is kept here
+    let mut failure = None;
+    out.for_each_entries(|entry, reader| {
+        let res = extract_entry(
+            entry,
+            reader,
+            to,
+            opts,
+            &mut report,
+            &mut copies,
+            &mut pending,
+            &mut budget,
+        )
+        .map_err(|err| read_error(err).with_entry(&entry_path(entry)));
+        let res = skip_unsafe(res, &mut report, opts)
+            // the decoder does not skip what an entry left unread
+            .and_then(|()| Ok(io::copy(reader, &mut io::sink()).map(|_| ())?));
+        match res {
+            Ok(()) => Ok(true),
+            Err(err) => {
+                failure = Some(err);
+                Ok(false)
+            }
+        }
+    })
+    .map_err(sevenz_error)?;
+    if let Some(err) = failure {
+        return Err(err);
+    }
+
+    copies.finish(to, opts, &mut report, &mut budget)?;
+    pending.finish(opts)?;
+    Ok(report)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn extract_entry(
+    entry: &SevenZArchiveEntry,
+    reader: &mut dyn Read,
+    to: &Path,
+    opts: &ExtractOpts,
+    report: &mut Decompression,
+    copies: &mut Copies,
+    pending: &mut PendingDirs,
+    budget: &mut Budget,
+) -> Result<(), DecompressError> {
+    // anti items mark deletions in update archives, there is nothing to extract
+    if entry.is_anti_item() {
+        return Ok(());
+    }
+
+    // strip prefixed components, like for tar
+    let filepath = entry_path(entry)
+        .components()
+        .skip(opts.strip)
+        .collect::<PathBuf>();
+    let outpath = safe_join(to, &filepath)?;
+    if to == outpath {
+        return Ok(());
+    }
+
+    if !(opts.filter)(outpath.as_path()) {
+        report.filtered.push(outpath.to_string_lossy().to_string());
+        return Ok(());
+    }
+
+    let outpath = (opts.map)(outpath.as_path()).into_owned();
+    ensure_contained(to, &outpath)?;
+    budget.entry(to, &outpath)?;
+
+    let times = entry_times(entry);
+    let mode = unix_mode(entry);
+    if entry.is_directory() {
+        prepare_outpath(to, &outpath)?;
+        fs::create_dir_all(&outpath)?;
+        pending.push(&outpath, mode.map(|mode| mode & 0o7777), times);
+        report.dirs.push(outpath.to_string_lossy().to_string());
+    } else if mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK) {
+        let link = Link::Symbolic(read_link_target(reader)?);
+        extract_link(to, &outpath, link, times.mtime, opts, copies, report)?;
+    } else {
+        let outpath = match resolve_existing(&outpath, times.mtime, opts, report)? {
+            Some(outpath) => outpath,
+            None => return Ok(()),
+        };
+        prepare_outpath(to, &outpath)?;
+
+        let mut outfile = fs::File::create(&outpath)?;
+        report.bytes_written += budget.copy(reader, &mut outfile)?;
+        report.files.push(outpath.to_string_lossy().to_string());
+        set_times(&outpath, times, opts)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mode) = mode {
+                let mode = normalize_mode(mode & 0o7777);
+                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A symlink stores its target as the entry content, which is bounded as it is read in memory
+fn read_link_target(reader: &mut dyn Read) -> Result<PathBuf, DecompressError> {
+    let mut target = String::new();
+    reader
+        .take(MAX_LINK_TARGET + 1)
+        .read_to_string(&mut target)?;
+    if target.len() as u64 > MAX_LINK_TARGET {
+        return Err(DecompressError::corrupt("symlink target too long"));
+    }
+    Ok(PathBuf::from(target))
+}
+
+/// Archives made on windows may separate folders with `\`
+fn entry_path(entry: &SevenZArchiveEntry) -> PathBuf {
+    PathBuf::from(entry.name().replace('\\', "/"))
+}
+
+/// The unix mode of an entry, recorded by archivers running on unix
+fn unix_mode(entry: &SevenZArchiveEntry) -> Option<u32> {
+    let attributes = entry.windows_attributes();
+    (entry.has_windows_attributes && attributes & UNIX_EXTENSION != 0).then_some(attributes >> 16)
+}
+
+fn entry_times(entry: &SevenZArchiveEntry) -> EntryTimes {
+    EntryTimes {
+        mtime: entry
+            .has_last_modified_date
+            .then(|| entry.last_modified_date().to_unix_time()),
+        atime: entry
+            .has_access_date
+            .then(|| entry.access_date().to_unix_time()),
+    }
+}
+
+fn sevenz_error(err: sevenz_rust::Error) -> DecompressError {
+    use sevenz_rust::Error;
+    match err {
+        Error::Io(err, _) | Error::FileOpen(err, _) => err.into(),
+        Error::ChecksumVerificationFailed | Error::NextHeaderCrcMismatch => {
+            DecompressError::ChecksumMismatch {
+                context: ErrorContext::default(),
+            }
+        }
+        Error::PasswordRequired => DecompressError::PasswordRequired {
+            context: ErrorContext::default(),
+        },
+        Error::MaybeBadPassword(_) => DecompressError::WrongPassword {
+            context: ErrorContext::default(),
+        },
+        Error::UnsupportedVersion { .. }
+        | Error::ExternalUnsupported
+        | Error::UnsupportedCompressionMethod(_)
+        | Error::Unsupported(_)
+        | Error::MaxMemLimited { .. } => DecompressError::unsupported(err.to_string()),
+        err => DecompressError::corrupt(err.to_string()),
+    }
+}
+
+/// Entries are read through plain readers, which fail with the errors of `sevenz_rust`
+/// (a checksum mismatch) inside IO errors
+fn read_error(err: DecompressError) -> DecompressError {
+    match err {
+        DecompressError::IO { source, .. }
+            if source
+                .get_ref()
+                .is_some_and(|inner| inner.is::<sevenz_rust::Error>()) =>
+        {
+            match source
+                .into_inner()
+                .map(|inner| inner.downcast::<sevenz_rust::Error>())
+            {
+                Some(Ok(inner)) => sevenz_error(*inner),
+                _ => unreachable!("checked to be a `sevenz_rust::Error`"),
+            }
+        }
+        err => err,
+    }
+}
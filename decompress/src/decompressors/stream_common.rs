@@ -0,0 +1,156 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use filetime::FileTime;
+use regex::Regex;
+
+use crate::decompressors::compose::{alternatives, cached_regex, Codec};
+use crate::decompressors::limits::Budget;
+use crate::decompressors::utils::{
+    listing, measure, prepare_outpath, resolve_existing, set_times, EntryTimes,
+};
+use crate::{DecompressError, Decompression, Decompressor, Entry, ExtractOpts, Listing};
+
+/// Name of the output of a single stream decompressor when reading from a reader,
+/// where there is no archive file name to derive it from
+pub const STREAM_NAME: &str = "data";
+
+/// The output name of a single stream archive, e.g. `sub.txt` for `sub.txt.gz`
+pub fn stream_name(archive: &Path) -> Result<&OsStr, DecompressError> {
+    archive
+        .file_stem()
+        .ok_or_else(|| DecompressError::not_a_file(archive))
+}
+
+/// The single entry of a stream archive, named after the output of its decompression
+pub fn stream_list(archive: &Path) -> Result<Vec<Entry>, DecompressError> {
+    Ok(vec![Entry {
+        path: PathBuf::from(stream_name(archive)?),
+        compressed_size: Some(fs::metadata(archive)?.len()),
+        ..Entry::default()
+    }])
+}
+
+/// Modification time of the archive file, which `gunzip` and friends give their output when
+/// the stream itself does not record one
+pub fn archive_times(archive: &Path) -> EntryTimes {
+    fs::metadata(archive)
+        .map(|md| EntryTimes::mtime(FileTime::from_last_modification_time(&md).unix_seconds()))
+        .unwrap_or_default()
+}
+
+pub fn stream_extract(
+    dec: impl Read,
+    to: &Path,
+    name: &OsStr,
+    times: EntryTimes,
+    opts: &ExtractOpts,
+    mut budget: Budget,
+) -> Result<Decompression, DecompressError> {
+    if !to.exists() {
+        fs::create_dir_all(to)?;
+    }
+    let mut report = Decompression::default();
+    let target = to.join(name);
+    budget.entry(to, &target)?;
+    let target = match resolve_existing(&target, times.mtime, opts, &mut report)? {
+        Some(target) => target,
+        None => return Ok(report),
+    };
+    prepare_outpath(to, &target)?;
+    let mut outfile = fs::File::create(&target)?;
+
+    report.bytes_written = budget.copy(&mut BufReader::new(dec), &mut outfile)?;
+    report.files.push(target.to_string_lossy().to_string());
+    set_times(&target, times, opts)?;
+    Ok(report)
+}
+
+///
+/// A single file compressed with a codec, unpacked to a file named after the archive
+/// without the codec's extension (`notes.txt.gz` to `notes.txt`), or after the name the
+/// stream records, when reading from a reader.
+pub struct Stream {
+    id: &'static str,
+    codec: Arc<dyn Codec>,
+    re: Regex,
+}
+
+impl Stream {
+    /// A codec on its own under `id`, `re` replaces the regex made of its extensions
+    #[must_use]
+    pub fn new(id: &'static str, codec: Arc<dyn Codec>, re: Option<Regex>) -> Self {
+        let re = re.unwrap_or_else(|| {
+            cached_regex(format!(r"(?i)\.({})$", alternatives(codec.extensions())))
+        });
+        Self { id, codec, re }
+    }
+
+    #[must_use]
+    pub fn build(id: &'static str, codec: Arc<dyn Codec>, re: Option<Regex>) -> Box<Self> {
+        Box::new(Self::new(id, codec, re))
+    }
+}
+
+impl Decompressor for Stream {
+    fn test_mimetype(&self, mimetype: &str) -> bool {
+        self.codec.mimetype() == Some(mimetype)
+    }
+
+    fn test(&self, archive: &Path) -> bool {
+        archive
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .is_some_and(|f| self.re.is_match(f))
+    }
+
+    fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
+        listing(self.id, || {
+            Ok(Listing {
+                id: self.id,
+                entries: stream_list(archive)?,
+                package: None,
+            })
+        })
+    }
+
+    fn decompress(
+        &self,
+        archive: &Path,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        measure(self.id, File::open(archive)?, opts, |reader, budget| {
+            let mut reader = BufReader::new(reader);
+            let times = self
+                .codec
+                .recorded(reader.fill_buf()?)
+                .mtime
+                .map_or_else(|| archive_times(archive), EntryTimes::mtime);
+            let dec = self.codec.decoder(Box::new(reader))?;
+            stream_extract(dec, to, stream_name(archive)?, times, opts, budget)
+        })
+    }
+
+    fn decompress_reader(
+        &self,
+        reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        measure(self.id, reader, opts, |reader, budget| {
+            let mut reader = BufReader::new(reader);
+            // the name a stream records is the best name we can get for it
+            let recorded = self.codec.recorded(reader.fill_buf()?);
+            let name = recorded.name.unwrap_or_else(|| OsString::from(STREAM_NAME));
+            let times = recorded.mtime.map(EntryTimes::mtime).unwrap_or_default();
+            let dec = self.codec.decoder(Box::new(reader))?;
+            stream_extract(dec, to, &name, times, opts, budget)
+        })
+    }
+}
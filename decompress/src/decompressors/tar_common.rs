@@ -1,73 +1,202 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::{
     fs::{self},
     io::{self, BufReader, Read},
     path::{Path, PathBuf},
 };
 
-use crate::decompressors::utils::normalize_mode;
-use crate::{DecompressError, ExtractOpts};
+use crate::decompressors::limits::Budget;
+use crate::decompressors::links::{extract_hardlink, extract_link, Copies, Link};
+use crate::decompressors::utils::{
+    ensure_contained, normalize_mode, prepare_outpath, resolve_existing, safe_join, set_times,
+    skip_unsafe, EntryTimes, PendingDirs,
+};
+use crate::{DecompressError, Decompression, Entry, EntryKind, ErrorContext, ExtractOpts};
 use tar::Archive;
 
-pub fn tar_list(out: &mut Archive<Box<dyn Read>>) -> Result<Vec<String>, DecompressError> {
-    Ok(out
-        .entries()?
-        .collect::<Result<Vec<_>, _>>()?
-        .iter()
-        .map(tar::Entry::path)
-        .collect::<Result<Vec<_>, _>>()?
-        .iter()
-        .map(|p| p.to_string_lossy().to_string())
-        .collect::<Vec<_>>())
+pub fn tar_list<R: Read>(out: &mut Archive<R>) -> Result<Vec<Entry>, DecompressError> {
+    let mut entries = vec![];
+    for entry in out.entries().map_err(tar_error)? {
+        let entry = entry.map_err(tar_error)?;
+        let header = entry.header();
+        entries.push(Entry {
+            path: entry.path()?.into_owned(),
+            kind: entry_kind(header.entry_type()),
+            size: Some(entry.size()),
+            mode: header.mode().ok(),
+            mtime: header.mtime().ok().and_then(|t| i64::try_from(t).ok()),
+            link_target: entry.link_name()?.map(Cow::into_owned),
+            uid: header.uid().ok(),
+            gid: header.gid().ok(),
+            ..Entry::default()
+        });
+    }
+    Ok(entries)
+}
+
+/// The `tar` crate reports malformed archives as plain IO errors, told apart by message
+fn tar_error(err: io::Error) -> DecompressError {
+    if err.kind() != io::ErrorKind::Other {
+        return err.into();
+    }
+    match err.to_string().as_str() {
+        "archive header checksum mismatch" => DecompressError::ChecksumMismatch {
+            context: ErrorContext::default(),
+        },
+        "failed to read entire block" | "unexpected EOF during skip" => {
+            DecompressError::Truncated {
+                context: ErrorContext::default(),
+            }
+        }
+        reason => DecompressError::corrupt(reason),
+    }
 }
 
-pub fn tar_extract(
-    out: &mut Archive<Box<dyn Read>>,
+fn entry_kind(entry_type: tar::EntryType) -> EntryKind {
+    if entry_type.is_file() || entry_type.is_contiguous() || entry_type.is_gnu_sparse() {
+        EntryKind::File
+    } else if entry_type.is_dir() {
+        EntryKind::Directory
+    } else if entry_type.is_symlink() {
+        EntryKind::Symlink
+    } else if entry_type.is_hard_link() {
+        EntryKind::Hardlink
+    } else {
+        EntryKind::Other
+    }
+}
+
+pub fn tar_extract<R: Read>(
+    out: &mut Archive<R>,
     to: &Path,
     opts: &ExtractOpts,
-) -> Result<Vec<String>, DecompressError> {
-    let mut files = vec![];
+    budget: &mut Budget,
+) -> Result<Decompression, DecompressError> {
+    let mut report = Decompression::default();
+    let mut copies = Copies::default();
+    let mut pending = PendingDirs::default();
+    let mut written = HashMap::new();
     if !to.exists() {
         fs::create_dir_all(to)?;
     }
 
     // alternative impl: just unpack, and then mv everything back X levels
-    for entry in out.entries()? {
-        let entry = entry?;
-        let filepath = entry.path()?;
-
-        // strip prefixed components. this can be 0 parts, in which case strip does not happen.
-        // it's done for when archives contain an enclosing folder
-        let filepath = filepath.components().skip(opts.strip).collect::<PathBuf>();
-
-        // because we potentially stripped a component, we may have an empty path, in which case
-        // the joined target will be identical to the target folder
-        // we take this approach to avoid hardcoding a check against empty ""
-        let outpath = to.join(filepath);
-        if to == outpath {
-            continue;
-        }
+    for entry in out.entries().map_err(tar_error)? {
+        let entry = entry.map_err(tar_error)?;
+        let name = PathBuf::from(String::from_utf8_lossy(&entry.path_bytes()).into_owned());
+        let res = extract_entry(
+            entry,
+            to,
+            opts,
+            &mut report,
+            &mut copies,
+            &mut pending,
+            &mut written,
+            budget,
+        )
+        .map_err(|err| err.with_entry(&name));
+        skip_unsafe(res, &mut report, opts)?;
+    }
+    copies.finish(to, opts, &mut report, budget)?;
+    pending.finish(opts)?;
+    Ok(report)
+}
 
-        if !(opts.filter)(outpath.as_path()) {
-            continue;
-        }
+/// Extract an entry. `written` maps the output path of each file extracted so far to where
+/// its data is, which hardlinks resolve against.
+#[allow(clippy::too_many_arguments)]
+fn extract_entry<R: Read>(
+    entry: tar::Entry<'_, R>,
+    to: &Path,
+    opts: &ExtractOpts,
+    report: &mut Decompression,
+    copies: &mut Copies,
+    pending: &mut PendingDirs,
+    written: &mut HashMap<PathBuf, PathBuf>,
+    budget: &mut Budget,
+) -> Result<(), DecompressError> {
+    let filepath = entry.path()?;
 
-        let outpath: Cow<'_, Path> = (opts.map)(outpath.as_path());
+    // strip prefixed components. this can be 0 parts, in which case strip does not happen.
+    // it's done for when archives contain an enclosing folder
+    let filepath = filepath.components().skip(opts.strip).collect::<PathBuf>();
 
-        if entry.header().entry_type() != tar::EntryType::Directory {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(p)?;
-                }
+    // because we potentially stripped a component, we may have an empty path, in which case
+    // the joined target will be identical to the target folder
+    // we take this approach to avoid hardcoding a check against empty ""
+    let outpath = safe_join(to, &filepath)?;
+    if to == outpath {
+        return Ok(());
+    }
+
+    if !(opts.filter)(outpath.as_path()) {
+        report.filtered.push(outpath.to_string_lossy().to_string());
+        return Ok(());
+    }
+
+    let outpath: Cow<'_, Path> = (opts.map)(outpath.as_path());
+    ensure_contained(to, &outpath)?;
+    budget.entry(to, &outpath)?;
+
+    let times = entry_times(entry.header());
+    match entry.header().entry_type() {
+        tar::EntryType::Directory => {
+            prepare_outpath(to, &outpath)?;
+            fs::create_dir_all(&outpath)?;
+            // the mode is applied last, a read-only directory would fail its children
+            pending.push(&outpath, entry.header().mode().ok(), times);
+            report.dirs.push(outpath.to_string_lossy().to_string());
+        }
+        tar::EntryType::Symlink => {
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| DecompressError::corrupt("symlink without a target"))?;
+            let link = Link::Symbolic(target.into_owned());
+            extract_link(to, &outpath, link, times.mtime, opts, copies, report)?;
+        }
+        tar::EntryType::Link => {
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| DecompressError::corrupt("hardlink without a target"))?;
+            // hardlinks point at another entry of the archive, which was stripped and
+            // mapped the same way when it was extracted
+            let target = target.components().skip(opts.strip).collect::<PathBuf>();
+            let target = (opts.map)(&safe_join(to, &target)?).into_owned();
+            ensure_contained(to, &target)?;
+            // a target left out by `strip`, `filter` or `overwrite` was not written, and a
+            // file that was already in `to` is not part of the archive
+            let target = written.get(&target).cloned();
+            extract_hardlink(
+                to,
+                &outpath,
+                target.clone(),
+                times.mtime,
+                opts,
+                copies,
+                report,
+            )?;
+            if let Some(target) = target {
+                written.insert(outpath.into_owned(), target);
             }
+        }
+        _ => {
+            let entrypath = outpath.into_owned();
+            let outpath = match resolve_existing(&entrypath, times.mtime, opts, report)? {
+                Some(outpath) => outpath,
+                None => return Ok(()),
+            };
+            prepare_outpath(to, &outpath)?;
 
             let mut outfile = fs::File::create(&outpath)?;
 
             #[cfg(unix)]
             let h = entry.header().mode();
 
-            io::copy(&mut BufReader::new(entry), &mut outfile)?;
-            files.push(outpath.to_string_lossy().to_string());
+            report.bytes_written += budget.copy(&mut BufReader::new(entry), &mut outfile)?;
+            report.files.push(outpath.to_string_lossy().to_string());
+            set_times(&outpath, times, opts)?;
+            written.insert(entrypath, outpath.clone());
 
             #[cfg(unix)]
             {
@@ -79,5 +208,17 @@ pub fn tar_extract(
             }
         }
     }
-    Ok(files)
+    Ok(())
+}
+
+fn entry_times(header: &tar::Header) -> EntryTimes {
+    // only GNU headers record an access time, zero means it was not recorded
+    let atime = header
+        .as_gnu()
+        .and_then(|h| h.atime().ok())
+        .filter(|t| *t > 0);
+    EntryTimes {
+        mtime: header.mtime().ok().and_then(|t| i64::try_from(t).ok()),
+        atime: atime.and_then(|t| i64::try_from(t).ok()),
+    }
 }
@@ -1,8 +1,19 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use crate::{DecompressError, Decompression, Decompressor, ExtractOpts, Listing};
+use crate::decompressors::limits::Budget;
+use crate::decompressors::utils::{
+    dos_to_unix, ensure_contained, listing, prepare_outpath, resolve_existing, safe_join,
+    set_times, skip_unsafe, EntryTimes, ReadCount,
+};
+use crate::{
+    DecompressError, Decompression, Decompressor, Entry, EntryKind, ErrorContext, ExtractOpts,
+    Listing,
+};
+use unrar::error::{Code, UnrarError, When};
+use unrar::FileHeader;
 
 lazy_static! {
     static ref RE: Regex = Regex::new(r"(?i)\.rar$").unwrap();
@@ -32,22 +43,31 @@ impl Decompressor for Unrar {
         archive
             .file_name()
             .and_then(std::ffi::OsStr::to_str)
-            .map_or(false, |f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
+            .is_some_and(|f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
     }
 
     fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
-        let res = unrar::Archive::new(archive.to_string_lossy().to_string())
-            .list()
-            .map_err(|e| DecompressError::Error(e.to_string()))?
-            .process()
-            .map_err(|e| DecompressError::Error(e.to_string()))?;
-
-        Ok(Listing {
-            id: "rar",
-            entries: res
-                .iter()
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<_>>(),
+        listing("rar", || {
+            let res = rar_entries(archive)?;
+            Ok(Listing {
+                id: "rar",
+                entries: res
+                    .iter()
+                    .map(|entry| Entry {
+                        path: entry.filename.clone(),
+                        kind: if entry.is_directory() {
+                            EntryKind::Directory
+                        } else {
+                            EntryKind::File
+                        },
+                        size: Some(entry.unpacked_size),
+                        mtime: entry_times(entry).mtime,
+                        crc: Some(entry.file_crc),
+                        ..Entry::default()
+                    })
+                    .collect::<Vec<_>>(),
+                package: None,
+            })
         })
     }
 
@@ -55,25 +75,182 @@ impl Decompressor for Unrar {
         &self,
         archive: &Path,
         to: &Path,
-        _opts: &ExtractOpts,
+        opts: &ExtractOpts,
     ) -> Result<Decompression, DecompressError> {
-        use std::fs;
-        if !to.exists() {
-            fs::create_dir_all(to)?;
+        rar_extract(archive, to, opts).map_err(|err| err.with_id("rar"))
+    }
+}
+
+fn rar_extract(
+    archive: &Path,
+    to: &Path,
+    opts: &ExtractOpts,
+) -> Result<Decompression, DecompressError> {
+    use std::fs;
+    if !to.exists() {
+        fs::create_dir_all(to)?;
+    }
+
+    let started = Instant::now();
+    let archive_size = fs::metadata(archive)?.len();
+    let mut report = Decompression {
+        id: "rar",
+        bytes_read: archive_size,
+        ..Decompression::default()
+    };
+
+    // unrar reads the archive itself, limits are checked against the archive size as read
+    let mut budget = Budget::for_opts(opts, ReadCount::new(archive_size));
+    let mut cursor = unrar::Archive::new(archive)
+        .open_for_processing()
+        .map_err(rar_error)?;
+    // entries are extracted one at a time, those left out are skipped without unpacking
+    while let Some(header) = cursor.read_header().map_err(rar_error)? {
+        let entry = header.entry();
+        let name = entry.filename.clone();
+        let (size, times) = (entry.unpacked_size, entry_times(entry));
+        let mut target = None;
+        let res = plan_entry(entry, to, opts, &mut report, &mut budget)
+            .map(|outpath| target = outpath)
+            .map_err(|err| err.with_entry(&name));
+        skip_unsafe(res, &mut report, opts)?;
+
+        cursor = match target {
+            Some(outpath) => {
+                let cursor = header
+                    .extract_to(&outpath)
+                    .map_err(|err| rar_error(err).with_entry(&name))?;
+                place_entry(&outpath, size, times, opts, &mut report, &mut budget)
+                    .map_err(|err| err.with_entry(&name))?;
+                cursor
+            }
+            None => header
+                .skip()
+                .map_err(|err| rar_error(err).with_entry(&name))?,
+        };
+    }
+    report.elapsed = started.elapsed();
+    Ok(report)
+}
+
+fn rar_entries(archive: &Path) -> Result<Vec<FileHeader>, DecompressError> {
+    unrar::Archive::new(archive)
+        .open_for_listing()
+        .map_err(rar_error)?
+        .map(|entry| entry.map_err(rar_error))
+        .collect()
+}
+
+/// An error of the unrar library, by its code
+fn rar_error(err: UnrarError) -> DecompressError {
+    let context = ErrorContext::default();
+    match (err.code, err.when) {
+        (Code::BadData, When::Process) => DecompressError::ChecksumMismatch { context },
+        (Code::BadData | Code::BadArchive, _) => DecompressError::corrupt(err.to_string()),
+        (Code::UnknownFormat, _) => DecompressError::unsupported(err.to_string()),
+        (Code::MissingPassword, _) => DecompressError::PasswordRequired { context },
+        (Code::BadPassword, _) => DecompressError::WrongPassword { context },
+        // a volume of a multi volume archive is missing
+        (Code::EOpen, When::Process) | (Code::EndArchive, _) => {
+            DecompressError::Truncated { context }
+        }
+        // a reference record whose source is not in the archive, or a comment too long
+        (Code::EReference | Code::SmallBuf | Code::Unknown | Code::Success, _) => {
+            DecompressError::corrupt(err.to_string())
         }
+        (Code::NoMemory, _) => DecompressError::unsupported(err.to_string()),
+        (Code::EOpen, _) => io_error(std::io::ErrorKind::NotFound, &err),
+        (Code::ECreate | Code::EClose | Code::ERead | Code::EWrite, _) => {
+            io_error(std::io::ErrorKind::Other, &err)
+        }
+    }
+}
 
-        let res = unrar::Archive::new(archive.to_string_lossy().to_string())
-            .extract_to(to.to_string_lossy().to_string())
-            .map_err(|e| DecompressError::Error(e.to_string()))?
-            .process()
-            .map_err(|e| DecompressError::Error(e.to_string()))?;
-
-        Ok(Decompression {
-            id: "rar",
-            files: res
-                .iter()
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<_>>(),
-        })
+/// A file system failure unrar reports by code only
+fn io_error(kind: std::io::ErrorKind, err: &UnrarError) -> DecompressError {
+    DecompressError::IO {
+        source: std::io::Error::new(kind, err.to_string()),
+        context: ErrorContext::default(),
+    }
+}
+
+/// Where a file entry is extracted to, `None` when it is stripped away, filtered out, left
+/// out by `ExtractOpts.overwrite`, or a folder, which is created here
+fn plan_entry(
+    entry: &FileHeader,
+    to: &Path,
+    opts: &ExtractOpts,
+    report: &mut Decompression,
+    budget: &mut Budget,
+) -> Result<Option<PathBuf>, DecompressError> {
+    use std::fs;
+
+    // strip prefixed components. this can be 0 parts, in which case strip does not happen.
+    // it's done for when archives contain an enclosing folder
+    let filepath = entry
+        .filename
+        .components()
+        .skip(opts.strip)
+        .collect::<PathBuf>();
+
+    // because we potentially stripped a component, we may have an empty path, in which case
+    // the joined target will be identical to the target folder
+    let outpath = safe_join(to, &filepath)?;
+    if to == outpath {
+        return Ok(None);
+    }
+
+    if !(opts.filter)(outpath.as_path()) {
+        report.filtered.push(outpath.to_string_lossy().to_string());
+        return Ok(None);
+    }
+
+    let outpath = (opts.map)(outpath.as_path()).into_owned();
+    ensure_contained(to, &outpath)?;
+    budget.entry(to, &outpath)?;
+
+    if entry.is_directory() {
+        prepare_outpath(to, &outpath)?;
+        fs::create_dir_all(&outpath)?;
+        report.dirs.push(outpath.to_string_lossy().to_string());
+        return Ok(None);
+    }
+
+    let times = entry_times(entry);
+    let outpath = match resolve_existing(&outpath, times.mtime, opts, report)? {
+        Some(outpath) => outpath,
+        None => return Ok(None),
+    };
+    // the size the entry records is checked before anything is written
+    budget.add(0, entry.unpacked_size)?;
+    prepare_outpath(to, &outpath)?;
+    Ok(Some(outpath))
+}
+
+/// Account for an entry unrar extracted to `outpath`, by what it wrote rather than the
+/// `size` the entry records
+fn place_entry(
+    outpath: &Path,
+    size: u64,
+    times: EntryTimes,
+    opts: &ExtractOpts,
+    report: &mut Decompression,
+    budget: &mut Budget,
+) -> Result<(), DecompressError> {
+    let written = std::fs::symlink_metadata(outpath)?.len();
+    if written > size {
+        budget.add(size, written - size)?;
+    }
+    report.bytes_written += written;
+    report.files.push(outpath.to_string_lossy().to_string());
+    set_times(outpath, times, opts)
+}
+
+/// DOS date in the high word, time in the low one
+#[allow(clippy::cast_possible_truncation)]
+fn entry_times(entry: &FileHeader) -> EntryTimes {
+    EntryTimes {
+        mtime: dos_to_unix((entry.file_time >> 16) as u16, entry.file_time as u16),
+        atime: None,
     }
 }
@@ -1,3 +1,65 @@
+use std::{
+    cell::Cell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use filetime::FileTime;
+
+// unrar reads archives itself, other decompressors are measured by what they read
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "deb",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    time::Instant,
+};
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "deb",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+use crate::decompressors::limits::Budget;
+use crate::{DecompressError, Decompression, ExtractOpts, Listing, OverwritePolicy};
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz"
+))]
 pub fn normalize_mode(mode: u32) -> u32 {
     if mode == 0 {
         0o644
@@ -5,3 +67,494 @@ pub fn normalize_mode(mode: u32) -> u32 {
         mode
     }
 }
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "rar"
+))]
+/// Returns `true` if a relative path can be joined onto a folder without escaping it
+fn is_contained(path: &Path) -> bool {
+    use std::path::Component;
+
+    path.components().all(|component| match component {
+        Component::ParentDir | Component::RootDir | Component::Prefix(..) => false,
+        Component::Normal(..) | Component::CurDir => true,
+    })
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "rar"
+))]
+/// Join an entry path, as found in an archive, onto the target folder, dropping `.`
+/// components (`./usr/bin` is common in tarballs).
+/// Absolute paths and paths with `..` are rejected, as they can write outside of `to`.
+///
+/// # Errors
+///
+/// Returns `DecompressError::UnsafePath` for an entry that escapes the target folder
+pub fn safe_join(to: &Path, entry: &Path) -> Result<PathBuf, DecompressError> {
+    if is_contained(entry) {
+        Ok(to.join(
+            entry
+                .components()
+                .filter(|component| component != &std::path::Component::CurDir)
+                .collect::<PathBuf>(),
+        ))
+    } else {
+        Err(DecompressError::unsafe_path(entry.to_path_buf()))
+    }
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "rar"
+))]
+/// Verify a final output path (e.g. one returned by `ExtractOpts.map`) is still inside `to`
+///
+/// # Errors
+///
+/// Returns `DecompressError::UnsafePath` for a path that escapes the target folder
+pub fn ensure_contained(to: &Path, path: &Path) -> Result<(), DecompressError> {
+    match path.strip_prefix(to) {
+        Ok(rest) if is_contained(rest) => Ok(()),
+        _ => Err(DecompressError::unsafe_path(path.to_path_buf())),
+    }
+}
+
+/// Verify that no folder between `to` and `path` is a symlink leading outside of `to`,
+/// so that a link extracted from an earlier entry cannot redirect a later one.
+///
+/// # Errors
+///
+/// Returns `DecompressError::UnsafePath` when writing `path` would go through such a link
+pub fn ensure_no_symlink_escape(to: &Path, path: &Path) -> Result<(), DecompressError> {
+    let rest = path
+        .strip_prefix(to)
+        .map_err(|_| DecompressError::unsafe_path(path.to_path_buf()))?;
+    let mut current = to.to_path_buf();
+    let mut components = rest.components().peekable();
+    while let Some(component) = components.next() {
+        // the leaf itself is replaced when written, only its ancestors are followed
+        if components.peek().is_none() {
+            break;
+        }
+        current.push(component);
+        let is_symlink = fs::symlink_metadata(&current)
+            .map(|md| md.file_type().is_symlink())
+            .unwrap_or(false);
+        if is_symlink && !current.canonicalize()?.starts_with(to.canonicalize()?) {
+            return Err(DecompressError::unsafe_path(path.to_path_buf()));
+        }
+    }
+    Ok(())
+}
+
+/// Prepare a path for writing an entry: check it cannot be redirected outside of `to`,
+/// create its parent folders and remove a link that is in its place, which would otherwise
+/// be followed when writing.
+///
+/// # Errors
+///
+/// Returns an error if the path is unsafe or on IO failure
+pub fn prepare_outpath(to: &Path, outpath: &Path) -> Result<(), DecompressError> {
+    ensure_no_symlink_escape(to, outpath)?;
+    if let Some(p) = outpath.parent() {
+        if !p.exists() {
+            fs::create_dir_all(p)?;
+        }
+    }
+    if fs::symlink_metadata(outpath).is_ok_and(|md| md.file_type().is_symlink()) {
+        fs::remove_file(outpath)?;
+    }
+    Ok(())
+}
+
+/// Decide where an entry goes when something already exists at `outpath`, following
+/// `ExtractOpts.overwrite`. Returns `None`, after recording it in `report`, when the entry is
+/// to be left out.
+///
+/// # Errors
+///
+/// Returns `DecompressError::FileExists` when conflicts are errors, or an error on IO failure
+pub fn resolve_existing(
+    outpath: &Path,
+    mtime: Option<i64>,
+    opts: &ExtractOpts,
+    report: &mut Decompression,
+) -> Result<Option<PathBuf>, DecompressError> {
+    let existing = match fs::symlink_metadata(outpath) {
+        Ok(existing) => existing,
+        Err(_) => return Ok(Some(outpath.to_path_buf())),
+    };
+    let keep = match opts.overwrite {
+        OverwritePolicy::Overwrite => false,
+        OverwritePolicy::Skip => true,
+        OverwritePolicy::Error => return Err(DecompressError::file_exists(outpath)),
+        OverwritePolicy::Newer => {
+            let existing = FileTime::from_last_modification_time(&existing).unix_seconds();
+            mtime.is_none_or(|mtime| mtime <= existing)
+        }
+        OverwritePolicy::Rename => {
+            let mut name = outpath.as_os_str().to_os_string();
+            name.push(".");
+            let renamed = (1..)
+                .map(|n| {
+                    let mut name = name.clone();
+                    name.push(n.to_string());
+                    PathBuf::from(name)
+                })
+                .find(|path| fs::symlink_metadata(path).is_err());
+            return Ok(renamed);
+        }
+    };
+    if keep {
+        report.skipped.push(outpath.to_string_lossy().to_string());
+        Ok(None)
+    } else {
+        Ok(Some(outpath.to_path_buf()))
+    }
+}
+
+#[cfg(any(feature = "zip", feature = "rar"))]
+/// Convert an MS-DOS date and time (zip, rar) to seconds since the unix epoch.
+/// DOS times carry no time zone, they are taken as UTC.
+pub fn dos_to_unix(date: u16, time: u16) -> Option<i64> {
+    let (y, m, d) = (
+        1980 + i64::from(date >> 9),
+        i64::from((date >> 5) & 0xf),
+        i64::from(date & 0x1f),
+    );
+    if m == 0 || d == 0 {
+        return None;
+    }
+    // days from civil, see http://howardhinnant.github.io/date_algorithms.html
+    let y = y - i64::from(m <= 2);
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    Some(
+        days * 86_400
+            + i64::from(time >> 11) * 3_600
+            + i64::from((time >> 5) & 0x3f) * 60
+            + i64::from(time & 0x1f) * 2,
+    )
+}
+
+/// Timestamps recorded for an entry, in seconds since the unix epoch
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EntryTimes {
+    pub mtime: Option<i64>,
+    pub atime: Option<i64>,
+}
+
+impl EntryTimes {
+    #[cfg(any(
+        feature = "ar",
+        feature = "rpm",
+        feature = "cpio",
+        feature = "gz",
+        feature = "bz2",
+        feature = "xz",
+        feature = "zstd",
+        feature = "lz4",
+        feature = "br",
+        feature = "lz",
+        feature = "lzma",
+        feature = "z"
+    ))]
+    #[must_use]
+    pub fn mtime(mtime: i64) -> Self {
+        Self {
+            mtime: Some(mtime),
+            atime: None,
+        }
+    }
+}
+
+/// Apply the timestamps of an entry to what was extracted, as far as `ExtractOpts` asks for
+///
+/// # Errors
+///
+/// Returns an error on IO failure
+pub fn set_times(
+    path: &Path,
+    times: EntryTimes,
+    opts: &ExtractOpts,
+) -> Result<(), DecompressError> {
+    let mtime = times
+        .mtime
+        .filter(|_| opts.preserve_mtime)
+        .map(|t| FileTime::from_unix_time(t, 0));
+    let atime = times
+        .atime
+        .filter(|_| opts.preserve_atime)
+        .map(|t| FileTime::from_unix_time(t, 0));
+    match (atime, mtime) {
+        (Some(atime), Some(mtime)) => filetime::set_file_times(path, atime, mtime)?,
+        (None, Some(mtime)) => filetime::set_file_mtime(path, mtime)?,
+        (Some(atime), None) => filetime::set_file_atime(path, atime)?,
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz"
+))]
+/// Directories whose attributes are applied once all entries were extracted: writing into a
+/// directory changes its modification time, and a read-only mode would fail later entries
+#[derive(Default)]
+pub struct PendingDirs {
+    dirs: Vec<(PathBuf, Option<u32>, EntryTimes)>,
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz"
+))]
+impl PendingDirs {
+    pub fn push(&mut self, path: &Path, mode: Option<u32>, times: EntryTimes) {
+        self.dirs.push((path.to_path_buf(), mode, times));
+    }
+
+    /// Apply attributes, deepest directories first so that parents are set last
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on IO failure
+    pub fn finish(mut self, opts: &ExtractOpts) -> Result<(), DecompressError> {
+        self.dirs
+            .sort_by_key(|(path, _, _)| std::cmp::Reverse(path.components().count()));
+        for (path, _mode, times) in self.dirs {
+            if !path.is_dir() {
+                continue;
+            }
+            set_times(&path, times, opts)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if let Some(mode) = _mode.filter(|mode| *mode != 0) {
+                    fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "rar"
+))]
+/// Record an entry left out for safety when `ExtractOpts.skip_unsafe` is set, any other
+/// outcome of extracting it is returned as is
+///
+/// # Errors
+///
+/// Returns the error of the entry, unless it was skipped
+pub fn skip_unsafe(
+    res: Result<(), DecompressError>,
+    report: &mut Decompression,
+    opts: &ExtractOpts,
+) -> Result<(), DecompressError> {
+    match res {
+        Err(DecompressError::UnsafePath { path, .. }) if opts.skip_unsafe => {
+            report.skipped.push(path.to_string_lossy().to_string());
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+/// Bytes read so far by a `CountingReader`
+#[derive(Debug, Clone, Default)]
+pub struct ReadCount(Rc<Cell<u64>>);
+
+impl ReadCount {
+    #[must_use]
+    pub fn new(count: u64) -> Self {
+        Self(Rc::new(Cell::new(count)))
+    }
+
+    #[must_use]
+    pub fn get(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "deb",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+/// A reader counting the bytes read through it
+pub struct CountingReader<R> {
+    inner: R,
+    count: ReadCount,
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "deb",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.0.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "deb",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+impl<R: Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(any(
+    feature = "tarball",
+    feature = "zip",
+    feature = "ar",
+    feature = "deb",
+    feature = "rpm",
+    feature = "cpio",
+    feature = "sevenz",
+    feature = "gz",
+    feature = "bz2",
+    feature = "xz",
+    feature = "zstd",
+    feature = "lz4",
+    feature = "br",
+    feature = "lz",
+    feature = "lzma",
+    feature = "z"
+))]
+/// Run an extraction from `reader`, completing its report with the decompressor `id`,
+/// the bytes read from `reader` and the time it took. The extraction is given a `Budget`
+/// for `ExtractOpts.limits` that follows what is read from `reader`.
+///
+/// # Errors
+///
+/// Returns the error of the extraction
+pub fn measure<R>(
+    id: &'static str,
+    reader: R,
+    opts: &ExtractOpts,
+    extract: impl FnOnce(CountingReader<R>, Budget) -> Result<Decompression, DecompressError>,
+) -> Result<Decompression, DecompressError> {
+    let started = Instant::now();
+    let count = ReadCount::default();
+    let budget = Budget::for_opts(opts, count.clone());
+    let res = extract(
+        CountingReader {
+            inner: reader,
+            count: count.clone(),
+        },
+        budget,
+    )
+    .map_err(|err| err.with_id(id))?;
+    Ok(Decompression {
+        id,
+        bytes_read: count.get(),
+        elapsed: started.elapsed(),
+        ..res
+    })
+}
+
+/// Run a listing by the decompressor `id`, which completes the context of its errors
+///
+/// # Errors
+///
+/// Returns the error of the listing
+pub fn listing(
+    id: &'static str,
+    list: impl FnOnce() -> Result<Listing, DecompressError>,
+) -> Result<Listing, DecompressError> {
+    list().map_err(|err| err.with_id(id))
+}
+
+#[cfg(feature = "deb")]
+/// Add what another extraction did to `report`, for archives extracted in several parts
+pub fn merge_report(report: &mut Decompression, other: Decompression) {
+    report.files.extend(other.files);
+    report.dirs.extend(other.dirs);
+    report.links.extend(other.links);
+    report.filtered.extend(other.filtered);
+    report.skipped.extend(other.skipped);
+    report.bytes_written += other.bytes_written;
+}
@@ -1,28 +1,78 @@
 use std::{
     fs::File,
-    io::{self, BufReader},
+    io::{self, BufReader, Read, Seek},
     path::{Path, PathBuf},
 };
 
 use lazy_static::lazy_static;
 use regex::Regex;
-use zip::ZipArchive;
+use zip::{read::ZipFile, result::ZipError, ZipArchive};
 
 use crate::{
-    decompressors::utils::normalize_mode, DecompressError, Decompression, Decompressor,
-    ExtractOpts, Listing,
+    decompressors::limits::Budget,
+    decompressors::links::{extract_link, Copies, Link},
+    decompressors::utils::{
+        dos_to_unix, ensure_contained, listing, measure, normalize_mode, prepare_outpath,
+        resolve_existing, safe_join, set_times, skip_unsafe, EntryTimes, PendingDirs,
+    },
+    DecompressError, Decompression, Decompressor, Entry, EntryKind, ErrorContext, ExtractOpts,
+    Listing,
 };
 
 lazy_static! {
-    static ref RE: Regex = Regex::new(r"(?i)\.zip$").unwrap();
+    static ref RE: Regex = Regex::new(r"(?i)\.(zip|jar|war|ear)$").unwrap();
 }
 
-fn build_archive(archive: &Path) -> Result<ZipArchive<BufReader<File>>, DecompressError> {
-    let rdr = ZipArchive::new(BufReader::new(File::open(archive)?))
-        .map_err(|err| DecompressError::Error(err.to_string()))?;
+// file type bits of a unix mode, as stored in the external attributes of an entry
+const S_IFMT: u32 = 0o170_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// Symlink targets are stored as entry data, anything longer is not a path
+const MAX_LINK_TARGET: u64 = 4096;
+
+fn build_archive<R: Read + Seek>(rdr: R) -> Result<ZipArchive<R>, DecompressError> {
+    let rdr = ZipArchive::new(rdr).map_err(zip_error)?;
 
     Ok(rdr)
 }
+
+/// A symlink stores its target as the entry content, which is bounded as it is read in memory
+fn read_link_target(file: &mut impl Read) -> Result<PathBuf, DecompressError> {
+    let mut target = String::new();
+    file.take(MAX_LINK_TARGET + 1).read_to_string(&mut target)?;
+    if target.len() as u64 > MAX_LINK_TARGET {
+        return Err(DecompressError::corrupt("symlink target too long"));
+    }
+    Ok(PathBuf::from(target))
+}
+
+/// An error of the `zip` crate, by what went wrong
+fn zip_error(err: ZipError) -> DecompressError {
+    match err {
+        ZipError::Io(err) => err.into(),
+        ZipError::InvalidArchive(reason) => DecompressError::corrupt(reason),
+        ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => {
+            DecompressError::PasswordRequired {
+                context: ErrorContext::default(),
+            }
+        }
+        ZipError::UnsupportedArchive(feature) => DecompressError::unsupported(feature),
+        ZipError::FileNotFound => DecompressError::corrupt(err.to_string()),
+    }
+}
+
+/// The `zip` crate checks the CRC32 of an entry as it is read, and fails the read with a
+/// plain IO error
+fn checksum_error(err: DecompressError) -> DecompressError {
+    match err {
+        DecompressError::IO { source, .. } if source.to_string() == "Invalid checksum" => {
+            DecompressError::ChecksumMismatch {
+                context: ErrorContext::default(),
+            }
+        }
+        err => err,
+    }
+}
 #[derive(Default)]
 pub struct Zip {
     re: Option<Regex>,
@@ -36,6 +86,22 @@ impl Zip {
     pub fn build(re: Option<Regex>) -> Box<Self> {
         Box::new(Self::new(re))
     }
+
+    /// Decompress a zip archive from a seekable reader, without spooling it to disk
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if unpacking fails.
+    pub fn decompress_seekable<R: Read + Seek>(
+        &self,
+        reader: R,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        measure("zip", reader, opts, |reader, budget| {
+            zip_extract(&mut build_archive(reader)?, to, opts, budget)
+        })
+    }
 }
 
 impl Decompressor for Zip {
@@ -47,22 +113,46 @@ impl Decompressor for Zip {
         archive
             .file_name()
             .and_then(std::ffi::OsStr::to_str)
-            .map_or(false, |f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
+            .is_some_and(|f| self.re.as_ref().unwrap_or(&*RE).is_match(f))
     }
 
     fn list(&self, archive: &Path) -> Result<Listing, DecompressError> {
-        let mut rdr = build_archive(archive)?;
-        let mut entries = vec![];
-        for i in 0..rdr.len() {
-            let file = rdr
-                .by_index(i)
-                .map_err(|err| DecompressError::Error(err.to_string()))?;
-            let filepath = file
-                .enclosed_name()
-                .ok_or_else(|| DecompressError::Error("Invalid file path".to_string()))?;
-            entries.push(filepath.to_string_lossy().to_string());
-        }
-        Ok(Listing { id: "zip", entries })
+        listing("zip", || {
+            let mut rdr = build_archive(BufReader::new(File::open(archive)?))?;
+            let mut entries = vec![];
+            for i in 0..rdr.len() {
+                let mut file = rdr.by_index(i).map_err(zip_error)?;
+                let mode = file.unix_mode();
+                let is_symlink = mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK);
+                let link_target = if is_symlink {
+                    Some(read_link_target(&mut file)?)
+                } else {
+                    None
+                };
+                entries.push(Entry {
+                    path: PathBuf::from(file.name()),
+                    kind: if file.is_dir() {
+                        EntryKind::Directory
+                    } else if is_symlink {
+                        EntryKind::Symlink
+                    } else {
+                        EntryKind::File
+                    },
+                    size: Some(file.size()),
+                    compressed_size: Some(file.compressed_size()),
+                    mode,
+                    mtime: entry_mtime(&file),
+                    link_target,
+                    crc: Some(file.crc32()),
+                    ..Entry::default()
+                });
+            }
+            Ok(Listing {
+                id: "zip",
+                entries,
+                package: None,
+            })
+        })
     }
 
     fn decompress(
@@ -71,62 +161,152 @@ impl Decompressor for Zip {
         to: &Path,
         opts: &ExtractOpts,
     ) -> Result<Decompression, DecompressError> {
-        use std::fs;
+        self.decompress_seekable(BufReader::new(File::open(archive)?), to, opts)
+    }
 
-        let mut files = vec![];
-        let mut rdr = build_archive(archive)?;
-        if !to.exists() {
-            fs::create_dir_all(to)?;
-        }
+    fn decompress_reader(
+        &self,
+        mut reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        // zip keeps its central directory at the end, so a stream has to be spooled first
+        let mut tmp = tempfile::tempfile()?;
+        io::copy(&mut reader, &mut tmp)?;
+        tmp.rewind()?;
+        self.decompress_seekable(BufReader::new(tmp), to, opts)
+    }
+}
 
-        for i in 0..rdr.len() {
-            let mut file = rdr
-                .by_index(i)
-                .map_err(|err| DecompressError::Error(err.to_string()))?;
-            let filepath = file
-                .enclosed_name()
-                .ok_or_else(|| DecompressError::Error("Invalid file path".to_string()))?;
-
-            // strip prefixed components. this can be 0 parts, in which case strip does not happen.
-            // it's done for when archives contain an enclosing folder
-            let filepath = filepath.components().skip(opts.strip).collect::<PathBuf>();
-
-            // because we potentially stripped a component, we may have an empty path, in which case
-            // the joined target will be identical to the target folder
-            // we take this approach to avoid hardcoding a check against empty ""
-            let outpath = to.join(filepath);
-            if outpath == to {
-                continue;
-            }
+fn zip_extract<R: Read + Seek>(
+    rdr: &mut ZipArchive<R>,
+    to: &Path,
+    opts: &ExtractOpts,
+    mut budget: Budget,
+) -> Result<Decompression, DecompressError> {
+    use std::fs;
 
-            if !(opts.filter)(outpath.as_path()) {
-                continue;
-            }
+    let mut report = Decompression::default();
+    let mut copies = Copies::default();
+    let mut pending = PendingDirs::default();
+    if !to.exists() {
+        fs::create_dir_all(to)?;
+    }
 
-            let outpath = (opts.map)(outpath.as_path());
-
-            if file.name().ends_with('/') {
-                fs::create_dir_all(&outpath)?;
-            } else {
-                if let Some(p) = outpath.parent() {
-                    if !p.exists() {
-                        fs::create_dir_all(p)?;
-                    }
-                }
-                let mut outfile = fs::File::create(&outpath)?;
-                io::copy(&mut file, &mut outfile)?;
-                files.push(outpath.to_string_lossy().to_string());
-            }
-            // Get and Set permissions
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Some(mode) = file.unix_mode() {
-                    let mode = normalize_mode(mode);
-                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
-                }
+    for i in 0..rdr.len() {
+        let file = rdr.by_index(i).map_err(zip_error)?;
+        let name = PathBuf::from(file.name());
+        let res = extract_entry(
+            file,
+            to,
+            opts,
+            &mut report,
+            &mut copies,
+            &mut pending,
+            &mut budget,
+        )
+        .map_err(|err| checksum_error(err).with_entry(&name));
+        skip_unsafe(res, &mut report, opts)?;
+    }
+    copies.finish(to, opts, &mut report, &mut budget)?;
+    pending.finish(opts)?;
+    Ok(report)
+}
+
+fn extract_entry(
+    mut file: ZipFile<'_>,
+    to: &Path,
+    opts: &ExtractOpts,
+    report: &mut Decompression,
+    copies: &mut Copies,
+    pending: &mut PendingDirs,
+    budget: &mut Budget,
+) -> Result<(), DecompressError> {
+    use std::fs;
+
+    let filepath = file
+        .enclosed_name()
+        .ok_or_else(|| DecompressError::unsafe_path(PathBuf::from(file.name())))?;
+
+    // strip prefixed components. this can be 0 parts, in which case strip does not happen.
+    // it's done for when archives contain an enclosing folder
+    let filepath = filepath.components().skip(opts.strip).collect::<PathBuf>();
+
+    // because we potentially stripped a component, we may have an empty path, in which case
+    // the joined target will be identical to the target folder
+    // we take this approach to avoid hardcoding a check against empty ""
+    let outpath = safe_join(to, &filepath)?;
+    if outpath == to {
+        return Ok(());
+    }
+
+    if !(opts.filter)(outpath.as_path()) {
+        report.filtered.push(outpath.to_string_lossy().to_string());
+        return Ok(());
+    }
+
+    let outpath = (opts.map)(outpath.as_path());
+    ensure_contained(to, &outpath)?;
+    budget.entry(to, &outpath)?;
+
+    let times = EntryTimes {
+        mtime: entry_mtime(&file),
+        atime: None,
+    };
+    if file.name().ends_with('/') {
+        prepare_outpath(to, &outpath)?;
+        fs::create_dir_all(&outpath)?;
+        pending.push(&outpath, file.unix_mode(), times);
+        report.dirs.push(outpath.to_string_lossy().to_string());
+    } else if file
+        .unix_mode()
+        .is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+    {
+        let link = Link::Symbolic(read_link_target(&mut file)?);
+        extract_link(to, &outpath, link, times.mtime, opts, copies, report)?;
+    } else {
+        let outpath = match resolve_existing(&outpath, times.mtime, opts, report)? {
+            Some(outpath) => outpath,
+            None => return Ok(()),
+        };
+        prepare_outpath(to, &outpath)?;
+        let mut outfile = fs::File::create(&outpath)?;
+        report.bytes_written += budget.copy(&mut file, &mut outfile)?;
+        report.files.push(outpath.to_string_lossy().to_string());
+
+        // Get and Set permissions
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mode) = file.unix_mode() {
+                let mode = normalize_mode(mode);
+                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
             }
         }
-        Ok(Decompression { id: "zip", files })
+        set_times(&outpath, times, opts)?;
+    }
+    Ok(())
+}
+
+/// Modification time of an entry: the extended timestamp field (UTC) when present, otherwise
+/// the DOS date and time, which carries no time zone and is taken as UTC
+fn entry_mtime(file: &ZipFile<'_>) -> Option<i64> {
+    let dt = file.last_modified();
+    extended_mtime(file.extra_data()).or_else(|| dos_to_unix(dt.datepart(), dt.timepart()))
+}
+
+fn extended_mtime(mut extra: &[u8]) -> Option<i64> {
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let size = usize::from(u16::from_le_bytes([extra[2], extra[3]]));
+        let data = extra.get(4..4 + size)?;
+        // 0x5455 is the extended timestamp field, bit 0 of its flags marks an mtime
+        if id == 0x5455 && data.len() >= 5 && data[0] & 1 == 1 {
+            return Some(i64::from(i32::from_le_bytes([
+                data[1], data[2], data[3], data[4],
+            ])));
+        }
+        extra = &extra[4 + size..];
     }
+    None
 }
@@ -22,14 +22,24 @@
 pub mod decompressors;
 
 use derive_builder::Builder;
+use lazy_static::lazy_static;
 use std::borrow::Cow;
-use std::{convert::Infallible, io, path::Path};
+use std::{
+    convert::Infallible,
+    fmt, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum DecompressError {
-    #[error("could not decompress: `{0}`")]
-    IO(#[from] io::Error),
+    #[error("could not decompress{context}: `{source}`")]
+    IO {
+        source: io::Error,
+        context: ErrorContext,
+    },
 
     #[error("could not decompress: `{0}`")]
     Error(String),
@@ -39,17 +49,376 @@ pub enum DecompressError {
 
     #[error("no compressor found")]
     MissingCompressor,
+
+    #[error("corrupt archive{context}: {reason}")]
+    Corrupt {
+        reason: String,
+        context: ErrorContext,
+    },
+
+    #[error("unsupported archive feature{context}: {feature}")]
+    Unsupported {
+        feature: String,
+        context: ErrorContext,
+    },
+
+    #[error("checksum mismatch{context}")]
+    ChecksumMismatch { context: ErrorContext },
+
+    #[error("path escapes the target folder: `{}`{context}", .path.display())]
+    UnsafePath {
+        path: PathBuf,
+        context: ErrorContext,
+    },
+
+    #[error("extraction limit exceeded{context}: {limit}")]
+    LimitExceeded { limit: Limit, context: ErrorContext },
+
+    #[error("password required{context}")]
+    PasswordRequired { context: ErrorContext },
+
+    #[error("wrong password{context}")]
+    WrongPassword { context: ErrorContext },
+
+    #[error("truncated input{context}")]
+    Truncated { context: ErrorContext },
+
+    #[error("file already exists: `{}`{context}", .path.display())]
+    FileExists {
+        path: PathBuf,
+        context: ErrorContext,
+    },
+
+    #[error("not a file: `{}`{context}", .path.display())]
+    NotAFile {
+        path: PathBuf,
+        context: ErrorContext,
+    },
+}
+
+/// Where an error happened, as far as it is known
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The archive, unless read from a reader
+    pub archive: Option<PathBuf>,
+    /// The entry of the archive, for an error about one
+    pub entry: Option<PathBuf>,
+    /// The id of the decompressor
+    pub id: Option<&'static str>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(archive) = &self.archive {
+            write!(f, " in `{}`", archive.display())?;
+        }
+        if let Some(entry) = &self.entry {
+            write!(f, " at `{}`", entry.display())?;
+        }
+        if let Some(id) = self.id {
+            write!(f, " ({id})")?;
+        }
+        Ok(())
+    }
+}
+
+impl DecompressError {
+    /// A corrupt archive, with no context yet
+    #[must_use]
+    pub fn corrupt(reason: impl Into<String>) -> Self {
+        Self::Corrupt {
+            reason: reason.into(),
+            context: ErrorContext::default(),
+        }
+    }
+
+    /// An archive using a feature that is not supported, with no context yet
+    #[must_use]
+    pub fn unsupported(feature: impl Into<String>) -> Self {
+        Self::Unsupported {
+            feature: feature.into(),
+            context: ErrorContext::default(),
+        }
+    }
+
+    /// A path escaping the target folder, with no context yet
+    #[must_use]
+    pub fn unsafe_path(path: impl Into<PathBuf>) -> Self {
+        Self::UnsafePath {
+            path: path.into(),
+            context: ErrorContext::default(),
+        }
+    }
+
+    /// An existing file in the way of an entry, with no context yet
+    #[must_use]
+    pub fn file_exists(path: impl Into<PathBuf>) -> Self {
+        Self::FileExists {
+            path: path.into(),
+            context: ErrorContext::default(),
+        }
+    }
+
+    /// A path that does not name a file, with no context yet
+    #[must_use]
+    pub fn not_a_file(path: impl Into<PathBuf>) -> Self {
+        Self::NotAFile {
+            path: path.into(),
+            context: ErrorContext::default(),
+        }
+    }
+
+    /// An exceeded limit, with no context yet
+    #[must_use]
+    pub fn limit_exceeded(limit: Limit) -> Self {
+        Self::LimitExceeded {
+            limit,
+            context: ErrorContext::default(),
+        }
+    }
+
+    /// Where the error happened, for the variants that know
+    #[must_use]
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::IO { context, .. }
+            | Self::Corrupt { context, .. }
+            | Self::Unsupported { context, .. }
+            | Self::ChecksumMismatch { context }
+            | Self::UnsafePath { context, .. }
+            | Self::LimitExceeded { context, .. }
+            | Self::PasswordRequired { context }
+            | Self::WrongPassword { context }
+            | Self::Truncated { context }
+            | Self::FileExists { context, .. }
+            | Self::NotAFile { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Fill the context with `f`, which keeps what is already known: it is filled on
+    /// the way out, from the entry to the archive
+    fn fill_context(mut self, f: impl FnOnce(&mut ErrorContext)) -> Self {
+        match &mut self {
+            Self::IO { context, .. }
+            | Self::Corrupt { context, .. }
+            | Self::Unsupported { context, .. }
+            | Self::ChecksumMismatch { context }
+            | Self::UnsafePath { context, .. }
+            | Self::LimitExceeded { context, .. }
+            | Self::PasswordRequired { context }
+            | Self::WrongPassword { context }
+            | Self::Truncated { context }
+            | Self::FileExists { context, .. }
+            | Self::NotAFile { context, .. } => f(context),
+            _ => {}
+        }
+        self
+    }
+
+    /// The archive the error happened in, unless already known
+    #[must_use]
+    pub fn with_archive(self, archive: &Path) -> Self {
+        self.fill_context(|context| {
+            context.archive.get_or_insert_with(|| archive.to_path_buf());
+        })
+    }
+
+    /// The entry the error happened at, unless already known
+    #[must_use]
+    pub fn with_entry(self, entry: &Path) -> Self {
+        self.fill_context(|context| {
+            context.entry.get_or_insert_with(|| entry.to_path_buf());
+        })
+    }
+
+    /// The id of the decompressor the error happened in, unless already known
+    #[must_use]
+    pub fn with_id(self, id: &'static str) -> Self {
+        self.fill_context(|context| {
+            context.id.get_or_insert(id);
+        })
+    }
+}
+
+impl From<io::Error> for DecompressError {
+    /// Decoders report corrupt and truncated data as IO errors, which are told apart by
+    /// their kind. Errors of this crate raised inside a reader are carried through.
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+            if let Some(Ok(inner)) = err.into_inner().map(|inner| inner.downcast::<Self>()) {
+                return *inner;
+            }
+            unreachable!("checked to be a `DecompressError`");
+        }
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated {
+                context: ErrorContext::default(),
+            },
+            io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => {
+                Self::corrupt(err.to_string())
+            }
+            _ => Self::IO {
+                source: err,
+                context: ErrorContext::default(),
+            },
+        }
+    }
+}
+
+impl From<DecompressError> for io::Error {
+    /// For errors raised inside a reader, turned back by `From<io::Error>`
+    fn from(err: DecompressError) -> Self {
+        match err {
+            DecompressError::IO { source, .. } => source,
+            err => Self::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// A limit of `ExtractOpts.limits` that an extraction exceeded, with its configured value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    TotalSize(u64),
+    EntrySize(u64),
+    Entries(usize),
+    Ratio(u64),
+    Depth(usize),
+    /// `Nested.total_size`, over all the nested archives of an extraction
+    NestedSize(u64),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TotalSize(max) => write!(f, "more than {max} bytes in total"),
+            Self::EntrySize(max) => write!(f, "an entry of more than {max} bytes"),
+            Self::Entries(max) => write!(f, "more than {max} entries"),
+            Self::Ratio(max) => write!(f, "a compression ratio above {max}"),
+            Self::Depth(max) => write!(f, "a path deeper than {max} components"),
+            Self::NestedSize(max) => write!(f, "more than {max} bytes in nested archives"),
+        }
+    }
+}
+
+/// How many bytes of a stream are read in order to detect its content
+const PEEK_SIZE: usize = 8192;
+
+/// How many bytes of a stream content detection may read, when looking into what it decodes to
+const MAX_PEEK_SIZE: u64 = 4 * 1024 * 1024;
+
+lazy_static! {
+    /// Content detection, with the formats `infer` does not know about
+    static ref INFER: infer::Infer = {
+        let mut info = infer::Infer::new();
+        info.add("application/x-lz4", "lz4", |buf| {
+            buf.starts_with(&[0x04, 0x22, 0x4d, 0x18])
+        });
+        info.add("application/x-lzma", "lzma", is_lzma_alone);
+        info
+    };
+}
+
+/// LZMA-alone has no magic, but nearly always the default properties, a dictionary size as
+/// `xz` and `lzma` write it (`2^n` or `2^n + 2^(n-1)`), and either an unknown size or one that
+/// fits in 56 bits
+fn is_lzma_alone(buf: &[u8]) -> bool {
+    if buf.len() < 13 || buf[0] != 0x5d {
+        return false;
+    }
+    let dict_size = u32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+    let low = dict_size & dict_size.wrapping_neg();
+    let rest = dict_size - low;
+    let dict_ok = dict_size != 0 && (rest == 0 || rest == low << 1);
+    dict_ok && (buf[5..13] == [0xff; 8] || buf[12] == 0)
 }
 
 pub type FilterFn = dyn Fn(&Path) -> bool;
 pub type MapFn = dyn Fn(&Path) -> Cow<'_, Path>;
 
+/// What to do with symlink and hardlink entries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkPolicy {
+    /// Create links. Later entries are never written through a link that leads outside of
+    /// the target folder, and hardlinks may only point at entries inside it.
+    #[default]
+    Create,
+    /// Leave links out
+    Skip,
+    /// Write a copy of what the link points to. The link must point inside the target folder.
+    Copy,
+    /// Fail when the archive contains a link
+    Error,
+}
+
+/// What to do when an entry is extracted onto an existing file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Keep the existing file and leave the entry out
+    Skip,
+    /// Fail with `DecompressError::FileExists`
+    Error,
+    /// Replace the existing file only if the entry is newer, by the modification time
+    /// recorded in the archive
+    Newer,
+    /// Write the entry next to the existing file, with a numbered suffix (`file.txt.1`)
+    Rename,
+}
+
+/// Bounds on what an extraction may write, to defend against decompression bombs.
+/// They are checked while data is written, `None` leaves a dimension unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Total bytes written, by the archive and the archives nested in it together
+    pub total_size: Option<u64>,
+    /// Bytes written for a single entry
+    pub entry_size: Option<u64>,
+    /// Entries extracted, not counting those left out by `filter`
+    pub entries: Option<usize>,
+    /// Bytes written for every byte read from the archive
+    pub ratio: Option<u64>,
+    /// Components of an output path, below the target folder
+    pub depth: Option<usize>,
+}
+
+/// Where archives found among the extracted entries are extracted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NestedPolicy {
+    /// Leave them as they are
+    #[default]
+    Keep,
+    /// Into a folder next to the archive, named after it without its extensions
+    /// (`lib/app.war` goes to `lib/app`, `lib/data.tar.gz` to `lib/data`), the archive is kept
+    Sibling,
+    /// Into a folder that replaces the archive, under its name (`lib/app.war/`)
+    InPlace,
+}
+
+/// Extraction of archives found among the extracted entries and recognized by the stack
+/// (EARs of WARs of JARs, firmware bundles of tarballs).
+/// Nested archives are extracted as a whole, without `strip`, `filter` and `map`, but with
+/// the other options. `limits` apply to each of them, except for `limits.total_size` which
+/// bounds what the archive and its nested archives write together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Nested {
+    pub policy: NestedPolicy,
+    /// Levels of archives in archives to extract, `1` extracts those in the archive only
+    pub depth: usize,
+    /// Bytes written by nested archives in total, over all levels
+    pub total_size: Option<u64>,
+}
+
 #[derive(Builder)]
 #[builder(pattern = "owned")]
 pub struct ExtractOpts {
     #[builder(default)]
     pub detect_content: bool,
 
+    /// Leading path components to remove from entry paths, for archives with an enclosing
+    /// folder. Members of an `ar` archive have no folders, they are never stripped.
     #[builder(default)]
     pub strip: usize,
 
@@ -58,6 +427,107 @@ pub struct ExtractOpts {
 
     #[builder(setter(custom), default = "Box::new(|path| Cow::from(path))")]
     pub map: Box<MapFn>,
+
+    #[builder(default)]
+    pub links: LinkPolicy,
+
+    #[builder(default)]
+    pub overwrite: OverwritePolicy,
+
+    /// Restore modification times recorded in the archive on extracted files and directories
+    #[builder(default)]
+    pub preserve_mtime: bool,
+
+    /// Restore access times, for archives that record them (GNU tar)
+    #[builder(default)]
+    pub preserve_atime: bool,
+
+    /// Leave out entries with an unsafe path and report them in `Decompression.skipped`,
+    /// instead of failing with `DecompressError::UnsafePath`
+    #[builder(default)]
+    pub skip_unsafe: bool,
+
+    /// Fail with `DecompressError::LimitExceeded` once the extraction exceeds these
+    #[builder(default)]
+    pub limits: Limits,
+
+    /// Extract the archives found among the extracted entries
+    #[builder(default)]
+    pub nested: Nested,
+
+    /// Given the path a nested archive was written to, choose if it is extracted
+    #[builder(setter(custom), default = "Box::new(|_| true)")]
+    pub nested_filter: Box<FilterFn>,
+
+    /// What the extractions a nested archive is extracted within wrote, for the limits over
+    /// all of them
+    #[builder(setter(skip))]
+    spent: Spent,
+}
+
+/// Bytes written by the extractions a nested archive is extracted within, read by the
+/// `Budget` of every decompressor
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(
+    not(any(
+        feature = "tarball",
+        feature = "zip",
+        feature = "ar",
+        feature = "deb",
+        feature = "rpm",
+        feature = "cpio",
+        feature = "sevenz",
+        feature = "gz",
+        feature = "bz2",
+        feature = "xz",
+        feature = "zstd",
+        feature = "lz4",
+        feature = "br",
+        feature = "lz",
+        feature = "lzma",
+        feature = "z",
+        feature = "rar"
+    )),
+    allow(dead_code)
+)]
+pub(crate) struct Spent {
+    /// By all of them, towards `Limits.total_size`
+    pub total: u64,
+    /// By nested archives, towards `nested_size`
+    pub nested: u64,
+    /// `Nested.total_size`, for a nested archive
+    pub nested_size: Option<u64>,
+}
+
+impl ExtractOpts {
+    /// Options for an archive extracted as a whole, without `strip`, `filter`, `map` and
+    /// nested archives
+    fn for_whole(&self) -> Self {
+        Self {
+            detect_content: self.detect_content,
+            strip: 0,
+            filter: Box::new(|_| true),
+            map: Box::new(|path| Cow::from(path)),
+            links: self.links,
+            overwrite: self.overwrite,
+            preserve_mtime: self.preserve_mtime,
+            preserve_atime: self.preserve_atime,
+            skip_unsafe: self.skip_unsafe,
+            limits: self.limits,
+            nested: Nested::default(),
+            nested_filter: Box::new(|_| true),
+            spent: self.spent,
+        }
+    }
+
+    /// Options for a nested archive, extracted as a whole within extractions that wrote
+    /// what is `spent`
+    fn for_nested(&self, spent: Spent) -> Self {
+        Self {
+            spent,
+            ..self.for_whole()
+        }
+    }
 }
 
 impl ExtractOptsBuilder {
@@ -73,18 +543,84 @@ impl ExtractOptsBuilder {
         self.map = Some(Box::new(value));
         self
     }
+    /// Given the path a nested archive was written to, choose if it is extracted
+    #[must_use]
+    pub fn nested_filter(mut self, value: impl Fn(&Path) -> bool + 'static) -> Self {
+        self.nested_filter = Some(Box::new(value));
+        self
+    }
 }
 
-#[derive(Debug)]
+/// A report of what an extraction did
+#[derive(Debug, Default)]
 pub struct Decompression {
     pub id: &'static str,
+    /// Files written, including links extracted as copies
     pub files: Vec<String>,
+    /// Directory entries of the archive that were created
+    pub dirs: Vec<String>,
+    /// Symlinks and hardlinks that were created
+    pub links: Vec<String>,
+    /// Entries left out by `ExtractOpts.filter`
+    pub filtered: Vec<String>,
+    /// Entries left out for safety: unsafe paths (see `ExtractOpts.skip_unsafe`), links
+    /// with `LinkPolicy::Skip` and existing files kept by `ExtractOpts.overwrite`
+    pub skipped: Vec<String>,
+    /// Total bytes written to files
+    pub bytes_written: u64,
+    /// Bytes read from the archive, which is compressed data for a compressed archive
+    pub bytes_read: u64,
+    pub elapsed: Duration,
+    /// Archives found in the output and unpacked in turn, see `Decompress::recursive` and
+    /// `ExtractOpts.nested`
+    pub nested: Vec<Decompression>,
+}
+
+/// The kind of an archive entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntryKind {
+    #[default]
+    File,
+    Directory,
+    Symlink,
+    Hardlink,
+    /// Device nodes, fifos and other special entries
+    Other,
+}
+
+/// An archive entry, as recorded in the archive headers.
+/// Fields that an archive format does not record are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    /// Uncompressed size
+    pub size: Option<u64>,
+    pub compressed_size: Option<u64>,
+    pub mode: Option<u32>,
+    /// Modification time, in seconds since the unix epoch
+    pub mtime: Option<i64>,
+    pub link_target: Option<PathBuf>,
+    pub uid: Option<u64>,
+    pub gid: Option<u64>,
+    pub crc: Option<u32>,
+}
+
+/// Package metadata, for archives that are software packages
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub release: Option<String>,
+    pub arch: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct Listing {
     pub id: &'static str,
-    pub entries: Vec<String>,
+    pub entries: Vec<Entry>,
+    /// Package metadata, when the archive is a package that records it (`rpm`)
+    pub package: Option<Package>,
 }
 
 ///
@@ -102,6 +638,14 @@ pub trait Decompressor {
     /// Test if this `Decompressor` can unpack an archive, given a mimetype.
     fn test_mimetype(&self, mimetype: &str) -> bool;
 
+    ///
+    /// Test if this `Decompressor` can unpack an archive, given its content from the start.
+    /// Called after `test_mimetype`, when decompressors share a mimetype (like `tar.gz` and
+    /// `gz`) they look into the content to tell which one it is. Takes any content by default.
+    fn test_content(&self, _content: &mut dyn Read) -> bool {
+        true
+    }
+
     ///
     /// Test if this `Decompressor` can unpack an archive, given a path.
     /// The convention is to use `Regex` internally to test a path, because this is
@@ -129,6 +673,25 @@ pub trait Decompressor {
         to: &Path,
         opts: &ExtractOpts,
     ) -> Result<Decompression, DecompressError>;
+
+    ///
+    /// Decompress an archive from a reader (stdin, an HTTP body, bytes in memory).
+    /// The default implementation spools the stream into a temporary file and calls
+    /// `decompress`, decompressors that can stream should override it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if unpacking fails.
+    fn decompress_reader(
+        &self,
+        mut reader: Box<dyn Read>,
+        to: &Path,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        let mut tmp = tempfile::NamedTempFile::new()?;
+        io::copy(&mut reader, tmp.as_file_mut())?;
+        self.decompress(tmp.path(), to, opts)
+    }
 }
 
 ///
@@ -136,39 +699,56 @@ pub trait Decompressor {
 ///
 pub struct Decompress {
     decompressors: Vec<Box<dyn Decompressor>>,
+    recursion: usize,
 }
 
 impl Default for Decompress {
     fn default() -> Self {
+        let mut decompressors: Vec<Box<dyn Decompressor>> = vec![
+            #[cfg(feature = "zip")]
+            Box::<decompressors::zip::Zip>::default(),
+        ];
+        // containers in every enabled codec go before the codecs on their own, if `gz` was
+        // placed above `tar.gz`, it would unpack and leave a tar archive.
+        #[cfg(any(feature = "tarball", feature = "cpio"))]
+        decompressors.extend(decompressors::compose::compose(
+            &decompressors::containers::all(),
+            &decompressors::codecs::all(),
+        ));
+        let rest: Vec<Box<dyn Decompressor>> = vec![
+            #[cfg(feature = "gz")]
+            Box::<decompressors::gz::Gz>::default(),
+            #[cfg(feature = "deb")]
+            Box::<decompressors::deb::Deb>::default(),
+            #[cfg(feature = "ar")]
+            Box::<decompressors::ar::Ar>::default(),
+            #[cfg(feature = "rpm")]
+            Box::<decompressors::rpm::Rpm>::default(),
+            #[cfg(feature = "bz2")]
+            Box::<decompressors::bz2::Bz2>::default(),
+            #[cfg(feature = "xz")]
+            Box::<decompressors::xz::Xz>::default(),
+            #[cfg(feature = "zstd")]
+            Box::<decompressors::zstd::Zstd>::default(),
+            #[cfg(feature = "lz4")]
+            Box::<decompressors::lz4::Lz4>::default(),
+            #[cfg(feature = "br")]
+            Box::<decompressors::br::Br>::default(),
+            #[cfg(feature = "lz")]
+            Box::<decompressors::lz::Lz>::default(),
+            #[cfg(feature = "lzma")]
+            Box::<decompressors::lzma::Lzma>::default(),
+            #[cfg(feature = "z")]
+            Box::<decompressors::z::Z>::default(),
+            #[cfg(feature = "rar")]
+            Box::<decompressors::unrar::Unrar>::default(),
+            #[cfg(feature = "sevenz")]
+            Box::<decompressors::sevenz::Sevenz>::default(),
+        ];
+        decompressors.extend(rest);
         Self {
-            decompressors: vec![
-                #[cfg(feature = "zip")]
-                Box::<decompressors::zip::Zip>::default(),
-                #[cfg(feature = "targz")]
-                Box::<decompressors::targz::Targz>::default(),
-                #[cfg(feature = "tarball")]
-                Box::<decompressors::tarball::Tarball>::default(),
-                #[cfg(feature = "tarxz")]
-                Box::<decompressors::tarxz::Tarxz>::default(),
-                #[cfg(feature = "tarbz")]
-                Box::<decompressors::tarbz::Tarbz>::default(),
-                #[cfg(feature = "tarzst")]
-                Box::<decompressors::tarzst::Tarzst>::default(),
-                // order is important, `gz` is placed only after the targz variant did not match
-                // if it's placed above targz, it will unpack and leave a tar archive.
-                #[cfg(feature = "gz")]
-                Box::<decompressors::gz::Gz>::default(),
-                #[cfg(feature = "ar")]
-                Box::<decompressors::ar::Ar>::default(),
-                #[cfg(feature = "bz2")]
-                Box::<decompressors::bz2::Bz2>::default(),
-                #[cfg(feature = "xz")]
-                Box::<decompressors::xz::Xz>::default(),
-                #[cfg(feature = "zstd")]
-                Box::<decompressors::zstd::Zstd>::default(),
-                #[cfg(feature = "rar")]
-                Box::<decompressors::unrar::Unrar>::default(),
-            ],
+            decompressors,
+            recursion: 0,
         }
     }
 }
@@ -186,22 +766,207 @@ impl Decompress {
         detect_content: bool,
     ) -> Result<&Box<dyn Decompressor>, DecompressError> {
         if detect_content {
-            let res = infer::get_from_path(archive.as_ref())?;
-            let mt = res.map(|t| t.mime_type());
-            mt.and_then(|mt| self.decompressors.iter().find(|dec| dec.test_mimetype(mt)))
+            self.find_by_content(&mut archive.as_ref())
         } else {
             println!("f: {:?} ", archive.as_ref());
             self.decompressors
                 .iter()
                 .find(|dec| dec.test(archive.as_ref()))
+                .ok_or(DecompressError::MissingCompressor)
+        }
+    }
+
+    /// Find a decompressor from the stack, given the first bytes of an archive. A compressed
+    /// container is only told apart from a single compressed file if `head` holds enough of
+    /// the stream to decode the start of the container.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no decompressor matches the content
+    #[allow(clippy::borrowed_box)]
+    pub fn find_decompressor_for_content(
+        &self,
+        mut head: &[u8],
+    ) -> Result<&Box<dyn Decompressor>, DecompressError> {
+        self.find_by_content(&mut head)
+    }
+
+    /// The first decompressor answering the mimetype of the content, which takes the content
+    #[allow(clippy::borrowed_box)]
+    fn find_by_content(
+        &self,
+        content: &mut impl Content,
+    ) -> Result<&Box<dyn Decompressor>, DecompressError> {
+        let mut head = Vec::with_capacity(PEEK_SIZE);
+        content
+            .open()?
+            .take(PEEK_SIZE as u64)
+            .read_to_end(&mut head)?;
+        // the formats `infer` knows go first, their magic is stronger than the heuristics
+        // of ours
+        let mimetype = infer::get(&head)
+            .or_else(|| INFER.get(&head))
+            .ok_or(DecompressError::MissingCompressor)?
+            .mime_type();
+        for dec in self
+            .decompressors
+            .iter()
+            .filter(|dec| dec.test_mimetype(mimetype))
+        {
+            if dec.test_content(&mut content.open()?) {
+                return Ok(dec);
+            }
         }
-        .ok_or(DecompressError::MissingCompressor)
+        Err(DecompressError::MissingCompressor)
     }
 
     /// Build given a custom stack of decompressors
     #[must_use]
     pub fn build(decompressors: Vec<Box<dyn Decompressor>>) -> Self {
-        Self { decompressors }
+        Self {
+            decompressors,
+            recursion: 0,
+        }
+    }
+
+    /// Keep unpacking the output while it is a single archive (`data.tar.gz.xz`, a zip whose
+    /// only member is a `.tar.gz`), up to `depth` layers below the archive. A layer is
+    /// unpacked where it would have been written, without being kept, and reported in
+    /// `Decompression.nested` of the layer it came out of. Layers are first extracted as a
+    /// whole into a staging folder in the target, `strip`, `filter`, `map` and `nested`
+    /// apply to the entries of the last layer.
+    #[must_use]
+    pub fn recursive(mut self, depth: usize) -> Self {
+        self.recursion = depth;
+        self
+    }
+
+    /// Extract a layer into `to` with `extract`. A layer holding a single archive is unpacked
+    /// in turn, `depth` layers down, the last layer is extracted with `opts`.
+    fn extract_layers(
+        &self,
+        to: &Path,
+        extract: &dyn Fn(&Path, &ExtractOpts) -> Result<Decompression, DecompressError>,
+        opts: &ExtractOpts,
+        depth: usize,
+    ) -> Result<Decompression, DecompressError> {
+        if depth > 0 {
+            if let Some(res) = self.unwrap_layer(to, extract, opts, depth)? {
+                return Ok(res);
+            }
+        }
+        let res = extract(to, opts)?;
+        self.with_nested(res, opts)
+    }
+
+    /// Extract a layer as a whole into a staging folder in `to`, and the file it holds when
+    /// it is a single archive. `None` when the layer is the last one, nothing is left of it
+    /// in `to` then.
+    fn unwrap_layer(
+        &self,
+        to: &Path,
+        extract: &dyn Fn(&Path, &ExtractOpts) -> Result<Decompression, DecompressError>,
+        opts: &ExtractOpts,
+        depth: usize,
+    ) -> Result<Option<Decompression>, DecompressError> {
+        fs::create_dir_all(to)?;
+        let stage = tempfile::Builder::new()
+            .prefix(".decompress")
+            .tempdir_in(to)?;
+        let mut res = extract(stage.path(), &opts.for_whole())?;
+        let ([file], []) = (&res.files[..], &res.links[..]) else {
+            return Ok(None);
+        };
+        let file = PathBuf::from(file);
+        let (Ok(inner), Ok(path)) = (
+            self.find_decompressor(&file, opts.detect_content),
+            file.strip_prefix(stage.path()),
+        ) else {
+            return Ok(None);
+        };
+
+        // the archive is unpacked where it would have been written, and reported there
+        let outpath = to.join(path);
+        let dir = outpath.parent().unwrap_or(to);
+        let nested = self
+            .extract_layers(
+                dir,
+                &|to, opts| inner.decompress(&file, to, opts),
+                opts,
+                depth - 1,
+            )
+            .map_err(|err| err.with_archive(&outpath))?;
+        let unstage = |paths: &[String]| {
+            paths
+                .iter()
+                .map(|path| {
+                    Path::new(path)
+                        .strip_prefix(stage.path())
+                        .map_or_else(|_| path.clone(), |path| to.join(path).display().to_string())
+                })
+                .collect()
+        };
+        res.files = unstage(&res.files);
+        res.dirs = unstage(&res.dirs);
+        res.nested.push(nested);
+        Ok(Some(res))
+    }
+
+    /// Extract the nested archives of `res` as `opts.nested` says
+    fn with_nested(
+        &self,
+        mut res: Decompression,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        let mut spent = Spent {
+            total: opts.spent.total + res.bytes_written,
+            nested: 0,
+            nested_size: opts.nested.total_size,
+        };
+        self.extract_nested(&mut res, opts, opts.nested.depth, &mut spent)?;
+        Ok(res)
+    }
+
+    /// Extract the archives among the files `res` wrote as `opts.nested` says, and theirs,
+    /// `depth` levels down. `spent` is what was written so far, nested archives included.
+    fn extract_nested(
+        &self,
+        res: &mut Decompression,
+        opts: &ExtractOpts,
+        depth: usize,
+        spent: &mut Spent,
+    ) -> Result<(), DecompressError> {
+        if depth == 0 || opts.nested.policy == NestedPolicy::Keep {
+            return Ok(());
+        }
+        for file in res.files.clone() {
+            let file = PathBuf::from(file);
+            if !(opts.nested_filter)(&file) {
+                continue;
+            }
+            // not `find_decompressor`, nearly all files are no archives
+            let dec = if opts.detect_content {
+                self.find_by_content(&mut file.as_path()).ok()
+            } else {
+                self.decompressors.iter().find(|dec| dec.test(&file))
+            };
+            let Some(dec) = dec else {
+                continue;
+            };
+            let nested_opts = opts.for_nested(*spent);
+            let inner = match opts.nested.policy {
+                NestedPolicy::Sibling => dec.decompress(&file, &sibling_dir(&file), &nested_opts),
+                _ => extract_in_place(&file, |archive| {
+                    dec.decompress(archive, &file, &nested_opts)
+                }),
+            };
+            let mut inner = inner.map_err(|err| err.with_archive(&file))?;
+            spent.total += inner.bytes_written;
+            spent.nested += inner.bytes_written;
+            self.extract_nested(&mut inner, opts, depth - 1, spent)?;
+            res.nested.push(inner);
+        }
+        Ok(())
     }
 
     /// List
@@ -216,6 +981,7 @@ impl Decompress {
     ) -> Result<Listing, DecompressError> {
         self.find_decompressor(archive.as_ref(), opts.detect_content)
             .and_then(|dec| dec.list(archive.as_ref()))
+            .map_err(|err| err.with_archive(archive.as_ref()))
     }
 
     /// Decompress with a decompressor that is selected based on file name (cheaper)
@@ -229,12 +995,63 @@ impl Decompress {
         to: P,
         opts: &ExtractOpts,
     ) -> Result<Decompression, DecompressError> {
-        self.find_decompressor(archive.as_ref(), opts.detect_content)
-            .and_then(|dec| dec.decompress(archive.as_ref(), to.as_ref(), opts))
+        let (archive, to) = (archive.as_ref(), to.as_ref());
+        let dec = self
+            .find_decompressor(archive, opts.detect_content)
+            .map_err(|err| err.with_archive(archive))?;
+        self.extract_layers(
+            to,
+            &|to, opts| dec.decompress(archive, to, opts),
+            opts,
+            self.recursion,
+        )
+        .map_err(|err| err.with_archive(archive))
+    }
+
+    /// Decompress from a reader, with a decompressor that is selected based on the
+    /// content of the stream (reads first 8kb, and up to 4mb of a compressed stream to tell a
+    /// compressed tarball from a compressed file).
+    ///
+    /// Single stream decompressors (`gz`, `bz2`, `xz`, `zstd`) have no archive file name to
+    /// derive their output from, so they write to `data` in `to`, unless the stream
+    /// records its original name (as gzip may).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an IO or parsing error happened
+    pub fn decompress_reader<R: Read + 'static, P: AsRef<Path>>(
+        &self,
+        reader: R,
+        to: P,
+        opts: &ExtractOpts,
+    ) -> Result<Decompression, DecompressError> {
+        let mut content = Rewind {
+            inner: reader,
+            seen: Vec::new(),
+        };
+        let dec = self.find_by_content(&mut content)?;
+        let mut reader = io::Cursor::new(content.seen).chain(content.inner);
+        if self.recursion == 0 {
+            let res = dec.decompress_reader(Box::new(reader), to.as_ref(), opts)?;
+            return self.with_nested(res, opts);
+        }
+        // a layer that is not a single archive is extracted again, the stream is kept to be
+        // read twice
+        let mut spool = tempfile::NamedTempFile::new()?;
+        io::copy(&mut reader, spool.as_file_mut())?;
+        self.extract_layers(
+            to.as_ref(),
+            &|to, opts| {
+                let reader = fs::File::open(spool.path())?;
+                dec.decompress_reader(Box::new(reader), to, opts)
+            },
+            opts,
+            self.recursion,
+        )
     }
 
     /// Returns `true` if any of the decompressors in the stack can decompress this
-    /// specific archive based on its content (reads first 8kb)
+    /// specific archive based on its content (reads first 8kb, and into compressed streams)
     ///
     /// # Errors
     /// May fail if cannot read the file
@@ -256,6 +1073,106 @@ impl Decompress {
     }
 }
 
+/// An archive for content detection, read from its start for each candidate decompressor
+trait Content {
+    fn open(&mut self) -> io::Result<Box<dyn Read + '_>>;
+}
+
+impl Content for &Path {
+    fn open(&mut self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(fs::File::open(*self)?.take(MAX_PEEK_SIZE)))
+    }
+}
+
+impl Content for &[u8] {
+    fn open(&mut self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(*self))
+    }
+}
+
+/// A stream keeping what content detection read, to replay it for each candidate and then
+/// for decompression
+struct Rewind<R> {
+    inner: R,
+    seen: Vec<u8>,
+}
+
+impl<R: Read> Content for Rewind<R> {
+    fn open(&mut self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(Replay {
+            rewind: self,
+            pos: 0,
+        }))
+    }
+}
+
+struct Replay<'a, R> {
+    rewind: &'a mut Rewind<R>,
+    pos: usize,
+}
+
+impl<R: Read> Read for Replay<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let seen = &self.rewind.seen;
+        if self.pos < seen.len() {
+            let n = buf.len().min(seen.len() - self.pos);
+            buf[..n].copy_from_slice(&seen[self.pos..self.pos + n]);
+            self.pos += n;
+            return Ok(n);
+        }
+        let left = MAX_PEEK_SIZE.saturating_sub(seen.len() as u64);
+        let n = (&mut self.rewind.inner).take(left).read(buf)?;
+        self.rewind.seen.extend_from_slice(&buf[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Extract the archive at `file` with `extract`, given where it was moved aside to, under
+/// its own name as decompressors go by it. Its entries may well have the same name, or it is
+/// replaced by a folder. The archive is put back if extraction fails.
+fn extract_in_place(
+    file: &Path,
+    extract: impl FnOnce(&Path) -> Result<Decompression, DecompressError>,
+) -> Result<Decompression, DecompressError> {
+    let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+        return Err(DecompressError::not_a_file(file));
+    };
+    let aside = tempfile::Builder::new()
+        .prefix(".decompress")
+        .tempdir_in(dir)?;
+    let archive = aside.path().join(name);
+    fs::rename(file, &archive)?;
+    extract(&archive).or_else(|err| {
+        fs::rename(&archive, file)?;
+        Err(err.with_archive(file))
+    })
+}
+
+/// The folder next to a nested archive it is extracted to, `app.war` goes to `app`, a
+/// compressed container loses both extensions (`inner.tar.gz` goes to `inner`), and names
+/// without an extension go to `name.d`
+fn sibling_dir(file: &Path) -> PathBuf {
+    match (file.file_stem(), file.extension()) {
+        (Some(stem), Some(_)) => {
+            let stem = Path::new(stem);
+            let is_container = stem
+                .extension()
+                .and_then(std::ffi::OsStr::to_str)
+                .is_some_and(|ext| ["tar", "cpio"].contains(&ext.to_ascii_lowercase().as_str()));
+            match stem.file_stem() {
+                Some(name) if is_container => file.with_file_name(name),
+                _ => file.with_file_name(stem),
+            }
+        }
+        _ => {
+            let mut name = file.as_os_str().to_owned();
+            name.push(".d");
+            PathBuf::from(name)
+        }
+    }
+}
+
 /// Decompress an archive with default decompressor set up
 ///
 /// # Errors
@@ -269,6 +1186,19 @@ pub fn decompress<P: AsRef<Path>>(
     Decompress::default().decompress(archive, to, opts)
 }
 
+/// Decompress an archive from a reader with default decompressor set up
+///
+/// # Errors
+///
+/// This function will return an error if IO or parsing failed
+pub fn decompress_reader<R: Read + 'static, P: AsRef<Path>>(
+    reader: R,
+    to: P,
+    opts: &ExtractOpts,
+) -> Result<Decompression, DecompressError> {
+    Decompress::default().decompress_reader(reader, to, opts)
+}
+
 /// List an archive with default decompressor set up
 ///
 /// # Errors
@@ -285,7 +1215,7 @@ pub fn can_decompress<P: AsRef<Path>>(archive: P) -> bool {
 }
 
 /// Returns `true` if any of the decompressors in the stack can decompress this
-/// specific archive based on its content (reads first 8kb)
+/// specific archive based on its content (reads first 8kb, and into compressed streams)
 ///
 /// # Errors
 /// May fail if cannot read the file
@@ -1,6 +1,11 @@
-use std::{fs, path::Path};
+use std::{fs, path::Path, sync::Arc, time::UNIX_EPOCH};
 
-use decompress::{decompressors, Decompress, DecompressError, Decompression, ExtractOptsBuilder};
+use decompress::decompressors::compose::{compose, Container};
+use decompress::decompressors::{codecs, containers};
+use decompress::{
+    decompressors, Decompress, DecompressError, Decompression, ExtractOptsBuilder, Limit, Limits,
+    LinkPolicy, Nested, NestedPolicy, OverwritePolicy,
+};
 use dircmp::Comparison;
 use insta::assert_debug_snapshot;
 use regex::Regex;
@@ -28,12 +33,45 @@ use rstest::rstest;
 #[case("inner.tar.xz", "inner_txz_1", 1, "tarxz")]
 #[case("inner.tar.zst", "inner_zst_1", 1, "tarzst")]
 #[case("inner.tar.bz2", "inner_bz2_1", 1, "tarbz")]
+#[case("inner.tar.lz4", "inner_lz4_1", 1, "tarlz4")]
+#[case("inner.tar.br", "inner_br_1", 1, "tarbr")]
+#[case("inner.tar.lz", "inner_lz_1", 1, "tarlz")]
+#[case("inner.tar.lzma", "inner_lzma_1", 1, "tarlzma")]
+#[case("inner.tar.Z", "inner_z_1", 1, "tarz")]
 #[case("bare.ar", "bare_ar", 0, "ar")]
 #[case("sub.txt.gz", "gz_1", 0, "gz")]
 #[case("sub.txt.bz2", "bz_2", 0, "bz2")]
 #[case("sub.txt.xz", "xz_1", 0, "xz")]
 #[case("sub.txt.zst", "zstd_1", 0, "zst")]
+#[case("sub.txt.lz4", "lz4_1", 0, "lz4")]
+#[case("sub.txt.br", "br_1", 0, "br")]
+#[case("sub.txt.lz", "lz_1", 0, "lz")]
+#[case("sub.txt.lzma", "lzma_1", 0, "lzma")]
+#[case("sub.txt.Z", "z_1", 0, "z")]
+#[case("words.txt.Z", "z_words", 0, "z")]
 #[case("version.rar", "rar_1", 0, "rar")]
+#[case("hello_xz.deb", "deb_xz", 0, "deb")]
+#[case("hello_gzip.deb", "deb_gzip", 0, "deb")]
+#[case("hello_zstd.deb", "deb_zstd", 0, "deb")]
+#[case("hello_bz2.deb", "deb_bz2", 0, "deb")]
+#[case("hello_lzma.deb", "deb_lzma", 0, "deb")]
+#[case("hello_gzip.rpm", "rpm_gzip", 0, "rpm")]
+#[case("hello_xz.rpm", "rpm_xz", 0, "rpm")]
+#[case("hello_zstd.rpm", "rpm_zstd", 0, "rpm")]
+#[case("hello_bzip2.rpm", "rpm_bzip2", 0, "rpm")]
+#[case("tree.cpio", "cpio_newc", 0, "cpio")]
+#[case("tree.cpio", "cpio_newc_1", 1, "cpio")]
+#[case("tree_crc.cpio", "cpio_crc", 0, "cpio")]
+#[case("tree_odc.cpio", "cpio_odc", 0, "cpio")]
+#[case("tree.cpio.gz", "cpio_gz", 0, "cpiogz")]
+#[case("tree.cpio.xz", "cpio_xz", 0, "cpioxz")]
+#[case("tree.cpio.zst", "cpio_zst", 0, "cpiozst")]
+#[case("tree.cpio.bz2", "cpio_bz", 0, "cpiobz")]
+#[case("initramfs.cpio", "cpio_sniffed_lz4", 0, "cpio")]
+#[case("tree_lzma2.7z", "sevenz_lzma2", 0, "sevenz")]
+#[case("tree_lzma2.7z", "sevenz_lzma2_1", 1, "sevenz")]
+#[case("tree_lzma.7z", "sevenz_lzma", 0, "sevenz")]
+#[case("tree_bcj.7z", "sevenz_bcj", 0, "sevenz")]
 #[trace]
 fn test_archives(
     #[case] archive: &str,
@@ -41,7 +79,7 @@ fn test_archives(
     #[case] strip: usize,
     #[case] id: &str,
 ) {
-    vec!["bare_zip_1", "bare_tgz_1", "bare_txz_1"]
+    ["bare_zip_1", "bare_tgz_1", "bare_txz_1"]
         .iter()
         .map(|p| format!("tests/expected/{p}"))
         .for_each(|p| {
@@ -67,6 +105,18 @@ fn test_archives(
 #[case("bare_zip", "content_bare_zip", "zip")]
 #[case("inner_tar_bz2", "content_inner_tar_bz2", "tarbz")]
 #[case("sub_txt_zst", "content_sub_txt_zst", "zst")]
+#[case("sub_txt_gz", "content_sub_txt_gz", "gz")]
+#[case("sub_txt_xz", "content_sub_txt_xz", "xz")]
+#[case("sub_txt_bz2", "content_sub_txt_bz2", "bz2")]
+#[case("tree_cpio_gz", "content_tree_cpio_gz", "cpiogz")]
+#[case("inner_tar_lz4", "content_inner_tar_lz4", "tarlz4")]
+#[case("inner_tar_lz", "content_inner_tar_lz", "tarlz")]
+#[case("inner_tar_lzma", "content_inner_tar_lzma", "tarlzma")]
+#[case("inner_tar_z", "content_inner_tar_z", "tarz")]
+#[case("hello_rpm", "content_hello_rpm", "rpm")]
+#[case("tree_7z", "content_tree_7z", "sevenz")]
+// an LZMA-alone header by its first bytes, but a tar archive
+#[case("bracket_tar", "content_bracket_tar", "tarball")]
 fn test_archives_content(#[case] archive: &str, #[case] outdir: &str, #[case] id: &str) {
     let extract_opts = ExtractOptsBuilder::default()
         .detect_content(true)
@@ -81,6 +131,303 @@ fn test_archives_content(#[case] archive: &str, #[case] outdir: &str, #[case] id
     assert_eq!(res.id, id);
 }
 
+#[rstest]
+#[case("inner.tar", "inner_0", "tarball")]
+#[case("bare.zip", "bare_zip_0", "zip")]
+#[case("folders.tar.gz", "folders_tgz_0", "targz")]
+#[case("folders.tar.xz", "folders_txz_0", "tarxz")]
+#[case("bare.ar", "bare_ar", "ar")]
+#[case("version.rar", "rar_1", "rar")]
+#[case("tree.cpio", "cpio_newc", "cpio")]
+#[case("tree_lzma2.7z", "sevenz_lzma2", "sevenz")]
+#[case("sub.txt.gz", "reader_sub_txt_gz", "gz")]
+#[case("inner.tar.bz2", "reader_inner_tar_bz2", "tarbz")]
+#[case("tree.cpio.gz", "reader_tree_cpio_gz", "cpiogz")]
+#[trace]
+fn test_archives_reader(#[case] archive: &str, #[case] outdir: &str, #[case] id: &str) {
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+
+    let res = assertion(archive, outdir, |from, to| {
+        Decompress::default().decompress_reader(fs::File::open(from).unwrap(), to, &extract_opts)
+    })
+    .unwrap();
+
+    assert_eq!(res.id, id);
+}
+
+#[test]
+fn test_stream_reader_name() {
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+    let out = "tests/out/gz_reader";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+
+    // no archive file name to derive the output from, so gz falls back to its header and
+    // the others to a fixed name
+    let dec = Decompress::build(vec![
+        decompressors::gz::Gz::build(None),
+        decompressors::zstd::Zstd::build(None),
+    ]);
+    let res = dec
+        .decompress_reader(
+            fs::File::open("tests/fixtures/sub.txt.gz").unwrap(),
+            out,
+            &extract_opts,
+        )
+        .unwrap();
+    assert_eq!(res.files, vec![format!("{out}/sub.txt")]);
+
+    let res = dec
+        .decompress_reader(
+            fs::File::open("tests/fixtures/sub.txt.zst").unwrap(),
+            out,
+            &extract_opts,
+        )
+        .unwrap();
+    assert_eq!(res.files, vec![format!("{out}/data")]);
+    assert_eq!(
+        fs::read(format!("{out}/data")).unwrap(),
+        fs::read("tests/expected/zstd_1/sub.txt").unwrap()
+    );
+}
+
+#[test]
+fn test_compose() {
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+    let tar: Arc<dyn Container> = Arc::new(containers::Tar);
+    let dec = Decompress::build(compose(
+        &[tar],
+        &[Arc::new(codecs::Lz4), Arc::new(codecs::Xz)],
+    ));
+
+    let res = assertion("inner.tar.xz", "compose_txz_0", |from, to| {
+        dec.decompress(from, to, &extract_opts)
+    })
+    .unwrap();
+    assert_eq!(res.id, "tarxz");
+    assert!(dec.can_decompress("a.tlz4"));
+    assert!(dec.can_decompress("a.tar"));
+    assert!(!dec.can_decompress("a.tar.gz"));
+}
+
+#[test]
+fn test_recursive() {
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+
+    // a zip of a .tar.gz.xz, unpacked down to the tarball's entries
+    let res = assertion("nested.zip", "nested_zip", |from, to| {
+        Decompress::default()
+            .recursive(3)
+            .decompress(from, to, &extract_opts)
+    })
+    .unwrap();
+    assert_eq!(res.id, "zip");
+    assert_eq!(res.nested.len(), 1);
+    assert_eq!(res.nested[0].id, "xz");
+    assert_eq!(res.nested[0].nested.len(), 1);
+    assert_eq!(res.nested[0].nested[0].id, "targz");
+    assert!(res.nested[0].nested[0].nested.is_empty());
+
+    // down to the depth asked for
+    let res = assertion("nested.zip", "nested_zip_1", |from, to| {
+        Decompress::default()
+            .recursive(1)
+            .decompress(from, to, &extract_opts)
+    })
+    .unwrap();
+    assert_eq!(res.nested[0].id, "xz");
+    assert!(res.nested[0].nested.is_empty());
+
+    // path options apply to the entries of the last layer, not to each layer
+    let strip_opts = ExtractOptsBuilder::default().strip(1).build().unwrap();
+    let res = assertion("nested.zip", "nested_zip_strip", |from, to| {
+        Decompress::default()
+            .recursive(3)
+            .decompress(from, to, &strip_opts)
+    })
+    .unwrap();
+    assert_eq!(res.nested[0].nested[0].id, "targz");
+    assert_eq!(res.nested[0].nested[0].files.len(), 2);
+
+    // a last layer is extracted with the options only, files in the way of its entries
+    // before they are mapped are left alone
+    for overwrite in [OverwritePolicy::Overwrite, OverwritePolicy::Error] {
+        let out = format!("tests/out/nested_map_{overwrite:?}");
+        if Path::new(&out).exists() {
+            fs::remove_dir_all(&out).unwrap();
+        }
+        fs::create_dir_all(&out).unwrap();
+        fs::write(format!("{out}/data.txt"), "USER FILE\n").unwrap();
+        let map_opts = ExtractOptsBuilder::default()
+            .map(|path| path.with_file_name("renamed.txt").into())
+            .overwrite(overwrite)
+            .build()
+            .unwrap();
+        let res = Decompress::default()
+            .recursive(2)
+            .decompress("tests/fixtures/one.tar.gz", &out, &map_opts)
+            .unwrap();
+        assert_eq!(res.files, vec![format!("{out}/renamed.txt")]);
+        assert!(res.nested.is_empty());
+        assert_eq!(
+            fs::read_to_string(format!("{out}/data.txt")).unwrap(),
+            "USER FILE\n"
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{out}/renamed.txt")).unwrap(),
+            "data\n"
+        );
+        assert_eq!(fs::read_dir(&out).unwrap().count(), 2);
+    }
+
+    // and not at all by default
+    let res = assertion("nested.zip", "nested_zip_0", |from, to| {
+        Decompress::default().decompress(from, to, &extract_opts)
+    })
+    .unwrap();
+    assert!(res.nested.is_empty());
+
+    // a stream from a reader has no name to go by, its layers are told by content
+    let extract_opts = ExtractOptsBuilder::default()
+        .detect_content(true)
+        .build()
+        .unwrap();
+    let res = assertion("inner.tar.gz.xz", "nested_reader", |from, to| {
+        Decompress::default().recursive(3).decompress_reader(
+            fs::File::open(from).unwrap(),
+            to,
+            &extract_opts,
+        )
+    })
+    .unwrap();
+    assert_eq!(res.id, "xz");
+    assert_eq!(res.nested[0].id, "targz");
+}
+
+#[rstest]
+#[case(NestedPolicy::Sibling, 2, "nested_sibling_2")]
+#[case(NestedPolicy::Sibling, 1, "nested_sibling_1")]
+#[case(NestedPolicy::InPlace, 2, "nested_in_place_2")]
+#[case(NestedPolicy::Keep, 2, "nested_keep")]
+#[trace]
+fn test_nested(#[case] policy: NestedPolicy, #[case] depth: usize, #[case] outdir: &str) {
+    let extract_opts = ExtractOptsBuilder::default()
+        .nested(Nested {
+            policy,
+            depth,
+            total_size: None,
+        })
+        .build()
+        .unwrap();
+
+    // a zip of a jar of a tarball
+    let res = assertion("bundle.zip", outdir, |from, to| {
+        Decompress::default().decompress(from, to, &extract_opts)
+    })
+    .unwrap();
+    let ids = |res: &Decompression| res.nested.iter().map(|n| n.id).collect::<Vec<_>>();
+    match policy {
+        NestedPolicy::Keep => assert!(res.nested.is_empty()),
+        _ => {
+            assert_eq!(ids(&res), vec!["zip"]);
+            assert_eq!(ids(&res.nested[0]), vec!["targz"; depth - 1]);
+        }
+    }
+}
+
+#[test]
+fn test_nested_bounds() {
+    let nested = Nested {
+        policy: NestedPolicy::Sibling,
+        depth: 2,
+        total_size: None,
+    };
+
+    // nested archives are chosen by a predicate
+    let extract_opts = ExtractOptsBuilder::default()
+        .nested(nested)
+        .nested_filter(|path| !path.to_string_lossy().ends_with(".tar.gz"))
+        .build()
+        .unwrap();
+    let res = assertion("bundle.zip", "nested_sibling_1_filter", |from, to| {
+        Decompress::default().decompress(from, to, &extract_opts)
+    })
+    .unwrap();
+    assert_eq!(res.nested.len(), 1);
+    assert!(res.nested[0].nested.is_empty());
+
+    // and bounded in size over all levels
+    let extract_opts = ExtractOptsBuilder::default()
+        .nested(Nested {
+            total_size: Some(100),
+            ..nested
+        })
+        .build()
+        .unwrap();
+    let res = Decompress::default().decompress(
+        "tests/fixtures/bundle.zip",
+        "tests/out/nested_total_size",
+        &extract_opts,
+    );
+    assert!(matches!(
+        res,
+        Err(DecompressError::LimitExceeded {
+            limit: Limit::NestedSize(100),
+            ..
+        })
+    ));
+
+    // the bound hit is reported: the bundle and its jar write 503 + 236 bytes, within the
+    // total size, the jar alone is over the nested size
+    let extract_opts = ExtractOptsBuilder::default()
+        .nested(Nested {
+            total_size: Some(100),
+            ..nested
+        })
+        .limits(Limits {
+            total_size: Some(503 + 236),
+            ..Limits::default()
+        })
+        .build()
+        .unwrap();
+    let res = Decompress::default().decompress(
+        "tests/fixtures/bundle.zip",
+        "tests/out/nested_total_size_both",
+        &extract_opts,
+    );
+    assert!(matches!(
+        res,
+        Err(DecompressError::LimitExceeded {
+            limit: Limit::NestedSize(100),
+            ..
+        })
+    ));
+
+    // nested archives write within the total size of the archive: 503 bytes of the bundle,
+    // and 236 of its jar, each within 600 bytes
+    let extract_opts = ExtractOptsBuilder::default()
+        .nested(nested)
+        .limits(Limits {
+            total_size: Some(600),
+            ..Limits::default()
+        })
+        .build()
+        .unwrap();
+    let res = Decompress::default().decompress(
+        "tests/fixtures/bundle.zip",
+        "tests/out/nested_outer_total_size",
+        &extract_opts,
+    );
+    assert!(matches!(
+        res,
+        Err(DecompressError::LimitExceeded {
+            limit: Limit::TotalSize(600),
+            ..
+        })
+    ));
+}
+
 #[test]
 fn test_custom() {
     let extract_opts = ExtractOptsBuilder::default().build().unwrap();
@@ -155,6 +502,829 @@ fn test_map(#[case] archive: &str, #[case] outdir: &str, #[case] id: &str) {
     assert_eq!(res.id, id);
 }
 
+#[rstest]
+#[case("truncated.txt.gz", "truncated", None, "gz")]
+#[case("corrupt.zip", "corrupt", None, "zip")]
+#[case("bad_crc.zip", "checksum", Some("hello.txt"), "zip")]
+#[case("encrypted.zip", "password", None, "zip")]
+#[case("long_link.zip", "corrupt", Some("link"), "zip")]
+#[case("long_link.7z", "corrupt", Some("long.txt"), "sevenz")]
+#[case("huge_dict.txt.lz", "corrupt", None, "lz")]
+#[case("huge_dict.txt.lzma", "unsupported", None, "lzma")]
+#[trace]
+fn test_error_context(
+    #[case] archive: &str,
+    #[case] kind: &str,
+    #[case] entry: Option<&str>,
+    #[case] id: &str,
+) {
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+    let out = format!("tests/out/errors/{archive}");
+    let archive = format!("tests/fixtures/{archive}");
+
+    let err = Decompress::default()
+        .decompress(archive.clone(), out, &extract_opts)
+        .unwrap_err();
+    assert!(
+        matches!(
+            (&err, kind),
+            (DecompressError::Truncated { .. }, "truncated")
+                | (DecompressError::Corrupt { .. }, "corrupt")
+                | (DecompressError::ChecksumMismatch { .. }, "checksum")
+                | (DecompressError::PasswordRequired { .. }, "password")
+                | (DecompressError::Unsupported { .. }, "unsupported")
+        ),
+        "unexpected error: {err:?}"
+    );
+    let context = err.context().unwrap();
+    assert_eq!(context.archive.as_deref(), Some(Path::new(&archive)));
+    assert_eq!(context.entry.as_deref(), entry.map(Path::new));
+    assert_eq!(context.id, Some(id));
+    if kind == "checksum" {
+        assert_eq!(
+            err.to_string(),
+            "checksum mismatch in `tests/fixtures/bad_crc.zip` at `hello.txt` (zip)"
+        );
+    }
+}
+
+#[rstest]
+#[case("traversal.tar", "tarball")]
+#[case("traversal.zip", "zip")]
+#[trace]
+fn test_path_traversal(#[case] archive: &str, #[case] id: &str) {
+    let out = format!("tests/out/traversal/{archive}");
+    if Path::new(&out).exists() {
+        fs::remove_dir_all(&out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+
+    let archive = format!("tests/fixtures/{archive}");
+    let res = Decompress::default().decompress(archive.clone(), out, &extract_opts);
+
+    // the error tells where it happened
+    match res {
+        Err(DecompressError::UnsafePath { path, context }) => {
+            assert_eq!(path, Path::new("../evil.txt"));
+            assert_eq!(context.archive.as_deref(), Some(Path::new(&archive)));
+            assert_eq!(context.entry.as_deref(), Some(Path::new("../evil.txt")));
+            assert_eq!(context.id, Some(id));
+        }
+        res => panic!("unexpected result: {res:?}"),
+    }
+    assert!(!Path::new("tests/out/traversal/evil.txt").exists());
+}
+
+#[rstest]
+#[case("traversal.tar")]
+#[case("traversal.zip")]
+#[trace]
+fn test_skip_unsafe(#[case] archive: &str) {
+    let out = format!("tests/out/skip_unsafe/{archive}");
+    if Path::new(&out).exists() {
+        fs::remove_dir_all(&out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default()
+        .skip_unsafe(true)
+        .build()
+        .unwrap();
+
+    let res = Decompress::default()
+        .decompress(
+            format!("tests/fixtures/{archive}"),
+            out.clone(),
+            &extract_opts,
+        )
+        .unwrap();
+
+    assert_eq!(res.files, vec![format!("{out}/ok.txt")]);
+    assert_eq!(res.skipped, vec!["../evil.txt".to_string()]);
+    assert!(!Path::new("tests/out/skip_unsafe/evil.txt").exists());
+}
+
+#[rstest]
+#[case("bomb.txt.gz", Limits { total_size: Some(65536), ..Limits::default() }, Limit::TotalSize(65536))]
+#[case("bomb.txt.gz", Limits { ratio: Some(100), ..Limits::default() }, Limit::Ratio(100))]
+#[case("inner.tar", Limits { entry_size: Some(20), ..Limits::default() }, Limit::EntrySize(20))]
+#[case("inner.zip", Limits { entries: Some(2), ..Limits::default() }, Limit::Entries(2))]
+#[case("inner.tar.gz", Limits { depth: Some(1), ..Limits::default() }, Limit::Depth(1))]
+#[case("version.rar", Limits { total_size: Some(1), ..Limits::default() }, Limit::TotalSize(1))]
+#[trace]
+fn test_limits(#[case] archive: &str, #[case] limits: Limits, #[case] exceeded: Limit) {
+    let out = format!("tests/out/limits/{archive}");
+    let extract_opts = ExtractOptsBuilder::default()
+        .limits(limits)
+        .build()
+        .unwrap();
+
+    let res = Decompress::default().decompress(
+        format!("tests/fixtures/{archive}"),
+        out.clone(),
+        &extract_opts,
+    );
+
+    assert!(matches!(res, Err(DecompressError::LimitExceeded { limit, .. }) if limit == exceeded));
+    // extraction stops while writing, not after the fact
+    if archive == "bomb.txt.gz" {
+        assert!(fs::metadata(format!("{out}/bomb.txt")).unwrap().len() < 1 << 20);
+    }
+}
+
+#[rstest]
+#[case("inner.tar", "folder-1/sub.txt")]
+#[case("inner.zip", "folder-1/sub.txt")]
+#[case("sub.txt.gz", "sub.txt")]
+#[case("bare.ar", "a.out")]
+#[case("version.rar", "VERSION")]
+#[trace]
+fn test_overwrite(#[case] archive: &str, #[case] entry: &str) {
+    let extract = |out: &str, overwrite: OverwritePolicy| {
+        let extract_opts = ExtractOptsBuilder::default()
+            .overwrite(overwrite)
+            .build()
+            .unwrap();
+        Decompress::default().decompress(
+            format!("tests/fixtures/{archive}"),
+            out.to_string(),
+            &extract_opts,
+        )
+    };
+    let fresh = |out: &str| {
+        if Path::new(out).exists() {
+            fs::remove_dir_all(out).unwrap();
+        }
+    };
+    let base = format!("tests/out/overwrite/{archive}/base");
+    fresh(&base);
+    extract(&base, OverwritePolicy::Overwrite).unwrap();
+    let original = fs::read(Path::new(&base).join(entry)).unwrap();
+
+    for overwrite in [
+        OverwritePolicy::Overwrite,
+        OverwritePolicy::Skip,
+        OverwritePolicy::Error,
+        OverwritePolicy::Newer,
+        OverwritePolicy::Rename,
+    ] {
+        let out = format!("tests/out/overwrite/{archive}/{overwrite:?}");
+        fresh(&out);
+        let existing = Path::new(&out).join(entry);
+        fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        fs::write(&existing, "local\n").unwrap();
+
+        let res = extract(&out, overwrite);
+        let content = fs::read(&existing).unwrap();
+        match overwrite {
+            OverwritePolicy::Overwrite => assert_eq!(content, original),
+            OverwritePolicy::Skip => {
+                assert_eq!(content, b"local\n");
+                assert!(res
+                    .unwrap()
+                    .skipped
+                    .contains(&existing.display().to_string()));
+            }
+            OverwritePolicy::Error => {
+                assert!(
+                    matches!(&res, Err(DecompressError::FileExists { path, .. }) if *path == existing)
+                );
+                let context = res.unwrap_err().context().cloned().unwrap();
+                let fixture = format!("tests/fixtures/{archive}");
+                assert_eq!(context.archive.as_deref(), Some(Path::new(&fixture)));
+                assert!(context.id.is_some());
+            }
+            OverwritePolicy::Newer => {
+                // the local file was just written, it is newer than the entry
+                assert_eq!(content, b"local\n");
+                filetime::set_file_mtime(&existing, filetime::FileTime::zero()).unwrap();
+                extract(&out, overwrite).unwrap();
+                assert_eq!(fs::read(&existing).unwrap(), original);
+            }
+            OverwritePolicy::Rename => {
+                assert_eq!(content, b"local\n");
+                let renamed = format!("{}.1", existing.display());
+                assert_eq!(fs::read(renamed).unwrap(), original);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rar_opts() {
+    let out = "tests/out/rar_opts";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let rar = decompressors::unrar::Unrar::default();
+    let decompress = |to: &str, extract_opts| {
+        use decompress::Decompressor;
+        rar.decompress(
+            Path::new("tests/fixtures/version.rar"),
+            Path::new(to),
+            &extract_opts,
+        )
+        .unwrap()
+    };
+
+    let res = decompress(
+        &format!("{out}/filter"),
+        ExtractOptsBuilder::default()
+            .filter(|path| !path.ends_with("VERSION"))
+            .build()
+            .unwrap(),
+    );
+    assert!(res.files.is_empty());
+    assert_eq!(res.filtered, vec![format!("{out}/filter/VERSION")]);
+    assert!(!Path::new(&format!("{out}/filter/VERSION")).exists());
+    // entries left out are skipped, not unpacked aside
+    assert_eq!(fs::read_dir(format!("{out}/filter")).unwrap().count(), 0);
+
+    let res = decompress(
+        &format!("{out}/map"),
+        ExtractOptsBuilder::default()
+            .map(|path| path.with_extension("txt").into())
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(res.files, vec![format!("{out}/map/VERSION.txt")]);
+    assert!(Path::new(&format!("{out}/map/VERSION.txt")).exists());
+
+    let res = decompress(
+        &format!("{out}/strip"),
+        ExtractOptsBuilder::default().strip(1).build().unwrap(),
+    );
+    assert!(res.files.is_empty());
+    assert_eq!(fs::read_dir(format!("{out}/strip")).unwrap().count(), 0);
+}
+
+#[test]
+fn test_ar_opts() {
+    let out = "tests/out/ar_opts";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default()
+        .strip(1)
+        .filter(|path| path.extension().is_some_and(|ext| ext == "o"))
+        .map(|path| {
+            let mut mapped = path.parent().unwrap().join("objs");
+            mapped.push(path.file_name().unwrap());
+            mapped.into()
+        })
+        .build()
+        .unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/members.ar", out, &extract_opts)
+        .unwrap();
+
+    assert_eq!(
+        res.files,
+        vec![format!("{out}/objs/one.o"), format!("{out}/objs/two.o")]
+    );
+    assert_eq!(res.filtered, vec![format!("{out}/README")]);
+    assert_eq!(
+        fs::read_to_string(format!("{out}/objs/two.o")).unwrap(),
+        "two\n"
+    );
+}
+
+#[rstest]
+#[case("libgnu.a", &["a_very_long_member_name_object.o", "short.txt"])]
+#[case("libbsd.a", &["a_very_long_member_name_object.o", "short.txt"])]
+#[case("libsymdef.a", &["short.txt"])]
+#[trace]
+fn test_ar_variants(#[case] archive: &str, #[case] members: &[&str]) {
+    let out = format!("tests/out/ar_variants/{archive}");
+    if Path::new(&out).exists() {
+        fs::remove_dir_all(&out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+    let res = Decompress::default()
+        .decompress(
+            format!("tests/fixtures/{archive}"),
+            out.clone(),
+            &extract_opts,
+        )
+        .unwrap();
+
+    let expected = members
+        .iter()
+        .map(|member| format!("{out}/{member}"))
+        .collect::<Vec<_>>();
+    assert_eq!(res.files, expected);
+    assert_eq!(fs::read_dir(&out).unwrap().count(), members.len());
+}
+
+#[test]
+fn test_deb_control() {
+    let out = "tests/out/deb_control";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let deb = decompressors::deb::Deb::default().with_control(format!("{out}/control"));
+    let res = Decompress::build(vec![Box::new(deb)])
+        .decompress(
+            "tests/fixtures/hello_xz.deb",
+            &format!("{out}/data"),
+            &ExtractOptsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+
+    assert!(res.files.contains(&format!("{out}/control/control")));
+    assert!(res.files.contains(&format!("{out}/data/usr/bin/hello")));
+    assert!(fs::read_to_string(format!("{out}/control/control"))
+        .unwrap()
+        .starts_with("Package: hello"));
+}
+
+#[test]
+fn test_rpm_hardlinks() {
+    let out = "tests/out/rpm_hardlinks";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let res = Decompress::default()
+        .decompress(
+            "tests/fixtures/hello_xz.rpm",
+            out,
+            &ExtractOptsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+
+    // the data is stored with the last entry of the inode, the first one links to it
+    let doc = format!("{out}/usr/share/doc/hello");
+    assert_eq!(
+        res.links,
+        vec![format!("{out}/usr/bin/hi"), format!("{doc}/README")]
+    );
+    assert!(res.files.contains(&format!("{doc}/README.link")));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let readme = fs::metadata(format!("{doc}/README")).unwrap();
+        let link = fs::metadata(format!("{doc}/README.link")).unwrap();
+        assert_eq!(readme.ino(), link.ino());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cpio_hardlinks() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    // the data of a hardlink left out goes to the first link kept
+    let out = "tests/out/cpio_hardlinks_filter";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default()
+        .filter(|path| !path.ends_with("hard2"))
+        .build()
+        .unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/tree.cpio", out, &extract_opts)
+        .unwrap();
+    assert_eq!(res.filtered, vec![format!("{out}/tree/hard2")]);
+    assert!(res.files.contains(&format!("{out}/tree/hard1")));
+    assert_eq!(
+        fs::read_to_string(format!("{out}/tree/hard1")).unwrap(),
+        "shared data\n"
+    );
+
+    // links to data that was not overwritten point to the file kept
+    let out = "tests/out/cpio_hardlinks_skip";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default()
+        .overwrite(OverwritePolicy::Skip)
+        .build()
+        .unwrap();
+    Decompress::default()
+        .decompress("tests/fixtures/tree.cpio", out, &extract_opts)
+        .unwrap();
+    fs::remove_file(format!("{out}/tree/hard1")).unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/tree.cpio", out, &extract_opts)
+        .unwrap();
+    assert!(res.skipped.contains(&format!("{out}/tree/hard2")));
+    assert_eq!(res.links, vec![format!("{out}/tree/hard1")]);
+    let hard1 = fs::metadata(format!("{out}/tree/hard1")).unwrap();
+    let hard2 = fs::metadata(format!("{out}/tree/hard2")).unwrap();
+    assert_eq!(hard1.ino(), hard2.ino());
+
+    // an inode whose data is not in the archive is skipped, one that is empty is created
+    // with its mode
+    let out = "tests/out/cpio_hardlinks_empty";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let res = Decompress::default()
+        .decompress(
+            "tests/fixtures/hardlinks.cpio",
+            out,
+            &ExtractOptsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+    assert_eq!(res.skipped, vec![format!("{out}/orphan")]);
+    assert!(!Path::new(out).join("orphan").exists());
+    assert_eq!(
+        res.files,
+        vec![format!("{out}/ok.txt"), format!("{out}/empty1")]
+    );
+    assert_eq!(res.links, vec![format!("{out}/empty2")]);
+    let empty1 = fs::metadata(format!("{out}/empty1")).unwrap();
+    assert_eq!(empty1.len(), 0);
+    assert_eq!(empty1.permissions().mode() & 0o777, 0o600);
+    assert_eq!(
+        empty1.ino(),
+        fs::metadata(format!("{out}/empty2")).unwrap().ino()
+    );
+
+    // links are refused before their data is looked for
+    let extract_opts = ExtractOptsBuilder::default()
+        .links(LinkPolicy::Error)
+        .filter(|path| !path.ends_with("empty1") && !path.ends_with("empty2"))
+        .build()
+        .unwrap();
+    let err = Decompress::default()
+        .decompress(
+            "tests/fixtures/hardlinks.cpio",
+            "tests/out/cpio_hardlinks_error",
+            &extract_opts,
+        )
+        .unwrap_err();
+    assert!(
+        matches!(err, DecompressError::Unsupported { .. }),
+        "unexpected error: {err:?}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cpio_raw_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let out = "tests/out/cpio_raw_names";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    Decompress::default()
+        .decompress(
+            "tests/fixtures/latin1.cpio",
+            out,
+            &ExtractOptsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+
+    // names are not decoded, `café.txt` in latin-1 keeps its bytes
+    let name = Path::new(out).join(OsStr::from_bytes(b"caf\xe9.txt"));
+    assert_eq!(fs::read_to_string(name).unwrap(), "latin1\n");
+}
+
+#[test]
+fn test_cpio_special() {
+    let out = "tests/out/cpio_special";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let res = Decompress::default()
+        .decompress(
+            "tests/fixtures/tree.cpio",
+            out,
+            &ExtractOptsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+
+    // device nodes are not recreated
+    assert_eq!(res.skipped, vec![format!("{out}/tree/null")]);
+    assert!(!Path::new(&format!("{out}/tree/null")).exists());
+    assert_eq!(
+        res.links,
+        vec![format!("{out}/tree/link"), format!("{out}/tree/hard1")]
+    );
+
+    let res = Decompress::default().decompress(
+        "tests/fixtures/corrupt_crc.cpio",
+        "tests/out/cpio_corrupt",
+        &ExtractOptsBuilder::default().build().unwrap(),
+    );
+    match res {
+        Err(DecompressError::ChecksumMismatch { context }) => {
+            assert_eq!(context.entry.as_deref(), Some(Path::new("tree/a.txt")));
+            assert_eq!(context.id, Some("cpio"));
+        }
+        res => panic!("unexpected result: {res:?}"),
+    }
+}
+
+#[rstest]
+#[case("bare.tar.gz")]
+#[case("bare.zip")]
+#[trace]
+fn test_map_cannot_escape(#[case] archive: &str) {
+    let extract_opts = ExtractOptsBuilder::default()
+        .map(|path| {
+            Path::new("tests/out/escaped")
+                .join(path.file_name().unwrap())
+                .into()
+        })
+        .build()
+        .unwrap();
+
+    let res = Decompress::default().decompress(
+        format!("tests/fixtures/{archive}"),
+        "tests/out/map_escape".to_string(),
+        &extract_opts,
+    );
+
+    assert!(matches!(res, Err(DecompressError::UnsafePath { .. })));
+    assert!(!Path::new("tests/out/escaped").exists());
+}
+
+#[cfg(unix)]
+#[rstest]
+#[case("links.tar", LinkPolicy::Create)]
+#[case("links.tar", LinkPolicy::Copy)]
+#[case("links.tar", LinkPolicy::Skip)]
+#[case("links.zip", LinkPolicy::Create)]
+#[case("links.zip", LinkPolicy::Copy)]
+#[case("links.zip", LinkPolicy::Skip)]
+#[trace]
+fn test_links(#[case] archive: &str, #[case] links: LinkPolicy) {
+    let out = format!("tests/out/{archive}_{links:?}");
+    if Path::new(&out).exists() {
+        fs::remove_dir_all(&out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default().links(links).build().unwrap();
+    Decompress::default()
+        .decompress(
+            format!("tests/fixtures/{archive}"),
+            out.clone(),
+            &extract_opts,
+        )
+        .unwrap();
+
+    let sym = Path::new(&out).join("dir/sym");
+    // zip has no hardlinks
+    let hard = archive
+        .ends_with(".tar")
+        .then(|| Path::new(&out).join("hard"));
+    match links {
+        LinkPolicy::Create => {
+            assert_eq!(fs::read_link(&sym).unwrap(), Path::new("file.txt"));
+            if let Some(hard) = hard {
+                assert_eq!(fs::read_to_string(hard).unwrap(), "linked\n");
+            }
+        }
+        LinkPolicy::Copy => {
+            assert!(!fs::symlink_metadata(&sym).unwrap().is_symlink());
+            assert_eq!(fs::read_to_string(&sym).unwrap(), "linked\n");
+            if let Some(hard) = hard {
+                assert_eq!(fs::read_to_string(hard).unwrap(), "linked\n");
+            }
+        }
+        _ => {
+            assert!(fs::symlink_metadata(&sym).is_err());
+            if let Some(hard) = hard {
+                assert!(fs::symlink_metadata(hard).is_err());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_links_error() {
+    let extract_opts = ExtractOptsBuilder::default()
+        .links(LinkPolicy::Error)
+        .build()
+        .unwrap();
+    let res = Decompress::default().decompress(
+        "tests/fixtures/links.tar",
+        "tests/out/links_error",
+        &extract_opts,
+    );
+    assert!(res.is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_report() {
+    let out = "tests/out/report";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default()
+        .links(LinkPolicy::Skip)
+        .filter(|path| !path.ends_with("hard"))
+        .build()
+        .unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/links.tar", out, &extract_opts)
+        .unwrap();
+
+    assert_eq!(res.files, vec![format!("{out}/dir/file.txt")]);
+    assert!(res.links.is_empty());
+    assert_eq!(res.skipped, vec![format!("{out}/dir/sym")]);
+    assert_eq!(res.filtered, vec![format!("{out}/hard")]);
+    assert_eq!(res.bytes_written, 7);
+    assert!(res.bytes_read > 0);
+    assert!(res.bytes_read <= fs::metadata("tests/fixtures/links.tar").unwrap().len());
+
+    let out = "tests/out/report_links";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/links.tar", out, &extract_opts)
+        .unwrap();
+
+    assert_eq!(
+        res.links,
+        vec![format!("{out}/dir/sym"), format!("{out}/hard")]
+    );
+    assert!(res.skipped.is_empty());
+
+    // a hardlink to an entry that was not extracted is skipped
+    let out = "tests/out/report_hard";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default()
+        .filter(|path| !path.ends_with("file.txt"))
+        .build()
+        .unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/links.tar", out, &extract_opts)
+        .unwrap();
+
+    assert_eq!(res.filtered, vec![format!("{out}/dir/file.txt")]);
+    assert_eq!(res.skipped, vec![format!("{out}/hard")]);
+    assert!(!Path::new(out).join("hard").exists());
+
+    // a file already in the target is not an entry of the archive, nothing links to it
+    let out = "tests/out/report_hard_existing";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    fs::create_dir_all(format!("{out}/dir")).unwrap();
+    fs::write(format!("{out}/dir/file.txt"), "mine\n").unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/links.tar", out, &extract_opts)
+        .unwrap();
+
+    assert_eq!(res.skipped, vec![format!("{out}/hard")]);
+    assert!(!Path::new(out).join("hard").exists());
+
+    // links are refused before their target is looked at
+    let extract_opts = ExtractOptsBuilder::default()
+        .links(LinkPolicy::Error)
+        .filter(|path| !path.ends_with("file.txt") && !path.ends_with("sym"))
+        .build()
+        .unwrap();
+    let err = Decompress::default()
+        .decompress(
+            "tests/fixtures/links.tar",
+            "tests/out/report_hard_error",
+            &extract_opts,
+        )
+        .unwrap_err();
+    assert!(
+        matches!(err, DecompressError::Unsupported { .. }),
+        "unexpected error: {err:?}"
+    );
+}
+
+#[cfg(unix)]
+#[rstest]
+#[case("symlink_escape.tar", LinkPolicy::Create)]
+#[case("symlink_escape.tar", LinkPolicy::Copy)]
+#[case("symlink_escape.zip", LinkPolicy::Create)]
+#[case("symlink_escape.zip", LinkPolicy::Copy)]
+#[trace]
+fn test_symlink_escape(#[case] archive: &str, #[case] links: LinkPolicy) {
+    let parent = format!("tests/out/{archive}_{links:?}");
+    let out = format!("{parent}/out");
+    if Path::new(&out).exists() {
+        fs::remove_dir_all(&out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default().links(links).build().unwrap();
+    let res =
+        Decompress::default().decompress(format!("tests/fixtures/{archive}"), out, &extract_opts);
+
+    assert!(matches!(res, Err(DecompressError::UnsafePath { .. })));
+    assert!(!Path::new(&format!("{parent}/pwned.txt")).exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_link_copies() {
+    // a link to a folder holding it would copy the folder into itself
+    let out = "tests/out/link_loop";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default()
+        .links(LinkPolicy::Copy)
+        .build()
+        .unwrap();
+    let res = Decompress::default().decompress("tests/fixtures/link_loop.tar", out, &extract_opts);
+    assert!(matches!(res, Err(DecompressError::UnsafePath { .. })));
+    assert!(!Path::new(out).join("a/loop/loop").exists());
+
+    let extract_opts = ExtractOptsBuilder::default()
+        .links(LinkPolicy::Copy)
+        .skip_unsafe(true)
+        .build()
+        .unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/link_loop.tar", out, &extract_opts)
+        .unwrap();
+    assert_eq!(res.files, vec![format!("{out}/a/f.txt")]);
+    assert_eq!(res.skipped, vec![format!("{out}/a/loop")]);
+
+    // the entries of a copied folder count against the limits, 5 entries copy 3 more
+    let extract_opts = ExtractOptsBuilder::default()
+        .links(LinkPolicy::Copy)
+        .limits(Limits {
+            entries: Some(5),
+            ..Limits::default()
+        })
+        .build()
+        .unwrap();
+    let res = Decompress::default().decompress(
+        "tests/fixtures/link_copies.tar",
+        "tests/out/link_copies",
+        &extract_opts,
+    );
+    assert!(
+        matches!(res, Err(DecompressError::LimitExceeded { limit, .. }) if limit == Limit::Entries(5))
+    );
+}
+
+#[rstest]
+#[case("times.tar", "d/f.txt", 1_500_000_000)]
+#[case("times.tar", "d", 1_600_000_000)]
+#[case("times.zip", "d/f.txt", 1_669_852_800)]
+#[case("times.zip", "d", 1_609_459_200)]
+#[case("sub.txt.gz", "sub.txt", 1_669_904_804)]
+#[trace]
+fn test_preserve_mtime(#[case] archive: &str, #[case] entry: &str, #[case] mtime: u64) {
+    let out = format!("tests/out/mtime_{archive}");
+    if Path::new(&out).exists() {
+        fs::remove_dir_all(&out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default()
+        .preserve_mtime(true)
+        .build()
+        .unwrap();
+    Decompress::default()
+        .decompress(
+            format!("tests/fixtures/{archive}"),
+            out.clone(),
+            &extract_opts,
+        )
+        .unwrap();
+
+    let modified = fs::metadata(Path::new(&out).join(entry))
+        .unwrap()
+        .modified()
+        .unwrap()
+        .duration_since(UNIX_EPOCH)
+        .unwrap();
+    assert_eq!(modified.as_secs(), mtime);
+}
+
+#[test]
+fn test_dirs() {
+    let out = "tests/out/dirs";
+    if Path::new(out).exists() {
+        fs::remove_dir_all(out).unwrap();
+    }
+    let extract_opts = ExtractOptsBuilder::default().build().unwrap();
+    let res = Decompress::default()
+        .decompress("tests/fixtures/dirs.tar", out, &extract_opts)
+        .unwrap();
+
+    assert_eq!(res.dirs, vec![format!("{out}/empty"), format!("{out}/ro")]);
+    assert!(Path::new(out).join("empty").is_dir());
+    assert_eq!(
+        fs::read_to_string(Path::new(out).join("ro/f.txt")).unwrap(),
+        "read only parent\n"
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let ro = Path::new(out).join("ro");
+        let mode = fs::metadata(&ro).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o555);
+        // let the next run clean up
+        fs::set_permissions(ro, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
 #[test]
 fn test_can_decompress() {
     assert!(Decompress::default().can_decompress("foo/bar/baz.tar.gz"));
@@ -174,6 +1344,23 @@ fn test_can_decompress() {
 #[case("sub.txt.bz2")]
 #[case("sub.txt.xz")]
 #[case("sub.txt.zst")]
+#[case("version.rar")]
+#[case("links.tar")]
+#[case("links.zip")]
+#[case("libgnu.a")]
+#[case("libbsd.a")]
+#[case("libsymdef.a")]
+#[case("hello_xz.deb")]
+#[case("hello_gzip.rpm")]
+#[case("tree.cpio")]
+#[case("tree_odc.cpio")]
+#[case("tree_lzma2.7z")]
+#[case("inner.tar.lz4")]
+#[case("sub.txt.lz4")]
+#[case("inner.tar.br")]
+#[case("sub.txt.lz")]
+#[case("inner.tar.lzma")]
+#[case("inner.tar.Z")]
 fn test_can_list(#[case] archive: &str) {
     let target = format!("tests/fixtures/{archive}");
     assert_debug_snapshot!(